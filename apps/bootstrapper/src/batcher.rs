@@ -0,0 +1,269 @@
+//! Batching of the contract calls made concurrently by the workers into
+//! Multicall3 `aggregate3` requests, so indexing many pairs doesn't cost a
+//! round-trip per call.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use color_eyre::eyre;
+use ethers::{
+    abi::Detokenize,
+    contract::{
+        multicall_contract::{Call3, Multicall3, Result as MulticallResult},
+        ContractCall, MULTICALL_ADDRESS,
+    },
+    providers::{Http, Middleware, Provider},
+    types::Bytes,
+};
+use tokio::{select, sync::oneshot};
+
+/// Time to wait for more calls before sending a batch which isn't full.
+const MAX_DELAY: Duration = Duration::from_millis(10);
+
+/// Failure of a single call of the batch.
+#[derive(Debug, Clone)]
+pub enum CallError {
+    /// The call reverted, or the target has no code.
+    Reverted(Bytes),
+
+    /// The whole batch failed, e.g. the node is unavailable.
+    Batch(String),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::Reverted(data) => write!(f, "Call reverted with data: {}", data),
+            CallError::Batch(err) => write!(f, "Batch of calls failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+struct PendingCall {
+    call: Call3,
+    reply: oneshot::Sender<Result<Bytes, CallError>>,
+}
+
+/// Sender of the calls to the batching task, cheap to clone.
+#[derive(Clone)]
+pub struct CallBatcher {
+    tx: flume::Sender<PendingCall>,
+}
+
+impl CallBatcher {
+    /// Return an error if Multicall3 isn't deployed on the chain of the
+    /// client.
+    pub async fn check_deployed(client: &Provider<Http>) -> eyre::Result<()> {
+        let code = client.get_code(MULTICALL_ADDRESS, None).await?;
+        if code.is_empty() {
+            eyre::bail!(
+                "Multicall3 isn't deployed at {:?} on this chain",
+                MULTICALL_ADDRESS
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Spawn the task which sends up to `batch_size` calls at once. The task
+    /// stops once all the clones of the batcher are dropped.
+    pub fn spawn(client: Arc<Provider<Http>>, batch_size: usize) -> Self {
+        let (tx, rx) = flume::unbounded();
+        let contract = Multicall3::new(MULTICALL_ADDRESS, client);
+
+        tokio::spawn(collect_batches(contract, rx, batch_size.max(1)));
+
+        Self { tx }
+    }
+
+    /// Make the call as part of a batch, return `None` if it reverted or its
+    /// output couldn't be decoded.
+    pub async fn try_call<D: Detokenize>(
+        &self,
+        call: ContractCall<Provider<Http>, D>,
+    ) -> eyre::Result<Option<D>> {
+        let Some(return_data) = self.try_call_raw(&call).await? else {
+            return Ok(None);
+        };
+
+        let output = call
+            .function
+            .decode_output(&return_data)
+            .ok()
+            .and_then(|tokens| D::from_tokens(tokens).ok());
+
+        Ok(output)
+    }
+
+    /// Make the call as part of a batch and return its undecoded output, or
+    /// `None` if it reverted.
+    pub async fn try_call_raw<D: Detokenize>(
+        &self,
+        call: &ContractCall<Provider<Http>, D>,
+    ) -> eyre::Result<Option<Bytes>> {
+        let target = *call
+            .tx
+            .to_addr()
+            .ok_or_else(|| eyre::eyre!("Call has no target address"))?;
+        let call_data = call.calldata().unwrap_or_default();
+
+        let (reply, result) = oneshot::channel();
+        self.tx
+            .send_async(PendingCall {
+                call: Call3 {
+                    target,
+                    allow_failure: true,
+                    call_data,
+                },
+                reply,
+            })
+            .await
+            .map_err(|_| eyre::eyre!("Batching task has stopped"))?;
+
+        match result.await? {
+            Ok(return_data) => Ok(Some(return_data)),
+            Err(CallError::Reverted(_)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Make the call as part of a batch, return an error if it reverted.
+    pub async fn call<D: Detokenize>(
+        &self,
+        call: ContractCall<Provider<Http>, D>,
+    ) -> eyre::Result<D> {
+        let name = call.function.name.clone();
+
+        self.try_call(call)
+            .await?
+            .ok_or_else(|| eyre::eyre!("Call of {} reverted", name))
+    }
+}
+
+/// Collect the pending calls into batches and send each of them concurrently.
+async fn collect_batches(
+    contract: Multicall3<Provider<Http>>,
+    rx: flume::Receiver<PendingCall>,
+    batch_size: usize,
+) {
+    while let Ok(first) = rx.recv_async().await {
+        let mut batch = vec![first];
+
+        let deadline = tokio::time::sleep(MAX_DELAY);
+        tokio::pin!(deadline);
+
+        while batch.len() < batch_size {
+            select! {
+                pending = rx.recv_async() => match pending {
+                    Ok(pending) => batch.push(pending),
+                    Err(_) => break,
+                },
+                _ = &mut deadline => break,
+            }
+        }
+
+        tokio::spawn(send_batch(contract.clone(), batch));
+    }
+}
+
+async fn send_batch(contract: Multicall3<Provider<Http>>, batch: Vec<PendingCall>) {
+    let calls = batch.iter().map(|pending| pending.call.clone()).collect();
+
+    tracing::debug!(calls = batch.len(), "Sending batch of calls");
+
+    match contract.aggregate_3(calls).call().await {
+        Ok(results) => {
+            for (
+                pending,
+                MulticallResult {
+                    success,
+                    return_data,
+                },
+            ) in batch.into_iter().zip(results)
+            {
+                // Calls to addresses without code succeed with no data.
+                let result = if success && !return_data.is_empty() {
+                    Ok(return_data)
+                } else {
+                    Err(CallError::Reverted(return_data))
+                };
+
+                // The caller could have given up waiting.
+                let _ = pending.reply.send(result);
+            }
+        }
+        Err(err) => {
+            let err = err.to_string();
+            for pending in batch {
+                let _ = pending.reply.send(Err(CallError::Batch(err.clone())));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bindings::erc20_mock::ERC20Mock;
+    use ethers::types::{Address, U256};
+    use futures::future::try_join_all;
+
+    use crate::testing;
+
+    use super::*;
+
+    /// Makes more calls at once than fit into a batch, and checks each of
+    /// them gets its own result, including the reverted one.
+    ///
+    /// Requires a local Anvil node at `ETH_RPC_URL` forked from a chain with
+    /// Multicall3 deployed:
+    ///
+    /// ```sh
+    /// anvil --fork-url $MAINNET_RPC_URL & cargo test -p bootstrapper -- --ignored
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires a local Anvil node"]
+    async fn test_batches_match_calls() -> eyre::Result<()> {
+        const TOKENS: usize = 5;
+
+        let provider = testing::anvil_provider()?;
+        CallBatcher::check_deployed(&provider).await?;
+
+        let sender = provider.get_accounts().await?[0];
+        let client = Arc::new(provider.clone().with_sender(sender));
+        let provider = Arc::new(provider);
+
+        let mut tokens = Vec::new();
+        for i in 0..TOKENS {
+            let symbol = format!("T{}", i);
+            let token = ERC20Mock::deploy(client.clone(), (symbol.clone(), symbol, 18u8))?
+                .send()
+                .await?;
+            token.mint(sender, U256::from(i)).send().await?.await?;
+
+            tokens.push(ERC20Mock::new(token.address(), provider.clone()));
+        }
+
+        let batcher = CallBatcher::spawn(provider.clone(), 2);
+
+        let symbols = try_join_all(tokens.iter().map(|token| batcher.call(token.symbol())));
+        let balances = try_join_all(
+            tokens
+                .iter()
+                .map(|token| batcher.call(token.balance_of(sender))),
+        );
+        // There is no code at the address, so the call counts as reverted.
+        let missing = ERC20Mock::new(Address::random(), provider.clone());
+
+        let (symbols, balances, missing_symbol) =
+            tokio::try_join!(symbols, balances, batcher.try_call(missing.symbol()))?;
+
+        for (i, (symbol, balance)) in symbols.iter().zip(&balances).enumerate() {
+            assert_eq!(*symbol, format!("T{}", i));
+            assert_eq!(*balance, U256::from(i));
+        }
+        assert_eq!(missing_symbol, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bindings::i_uniswap_v2erc20::IUniswapV2ERC20;
+use clap::Args;
+use color_eyre::eyre;
+use database::{tokens::TokenEntry, DB};
+use ethers::{
+    abi::Address,
+    providers::{Http, Provider},
+    types::U256,
+    utils::keccak256,
+};
+use futures::StreamExt;
+use sqlx::{types::BigDecimal, PgConnection};
+
+use config::Config;
+
+/// Denominator of the transfer tax, gives the precision of 0.01%.
+const TAX_DENOMINATOR: u32 = 10_000;
+
+/// Fraction of the pair balance transferred out of it, 0.1%.
+const TRANSFER_DIVISOR: u64 = 1_000;
+
+/// Gas limit of the simulated transfers, set so reverting transfers are
+/// mined instead of failing the gas estimation.
+const TRANSFER_GAS: u64 = 500_000;
+
+/// Time skipped to find the tokens which balances change by themselves.
+const REBASE_PERIOD: u64 = 24 * 60 * 60;
+
+#[derive(Args, Debug)]
+pub struct ClassifyArgs {
+    /// URL to the Anvil node forking the chain. Its state is reverted once
+    /// the tokens are classified.
+    #[arg(short = 'e', long = "eth-url")]
+    pub ethereum_provider_url: String,
+
+    /// Classify again the tokens which are already classified.
+    #[arg(short, long)]
+    pub all: bool,
+}
+
+/// Transfers of the token simulated through its pair.
+struct TransferProbe {
+    token_id: i32,
+    contract: IUniswapV2ERC20<Provider<Http>>,
+
+    /// Account which received the tokens from the pair.
+    holder: Address,
+
+    /// Balance of the holder after it sent a part back to the pair.
+    kept: U256,
+
+    /// The larger of the taxes of the transfers out of and into the pair.
+    tax_bps: u32,
+}
+
+/// Classify the tokens by simulating transfers on a forked node, as routes
+/// through fee-on-transfer or rebasing tokens return less than the constant
+/// product formula predicts.
+///
+/// For each token the share of its deepest pair balance is transferred from
+/// the pair to a fresh account, and half of the received amount back, which
+/// gives the taxes of both directions. Then the time is skipped, and tokens
+/// which changed the balance of the account are flagged as rebasing.
+pub async fn classify(
+    config: Config,
+    ClassifyArgs {
+        ethereum_provider_url,
+        all,
+    }: ClassifyArgs,
+) -> eyre::Result<()> {
+    let database = DB::from_url(&config.database.url).await?;
+    let eth_client = Arc::new(Provider::<Http>::try_from(ethereum_provider_url.as_str())?);
+
+    let mut conn = database.pool().acquire().await?;
+    let tokens: Vec<TokenEntry> = DB::tokens(&mut conn)
+        .await?
+        .into_iter()
+        .filter(|token| all || !token.is_classified())
+        .collect();
+    let pairs = deepest_pairs(&mut conn).await?;
+
+    tracing::info!("Classifying {} tokens", tokens.len());
+
+    let snapshot: U256 = eth_client.request("evm_snapshot", ()).await?;
+    let result = probe_tokens(&eth_client, &tokens, &pairs).await;
+    let reverted: bool = eth_client.request("evm_revert", [snapshot]).await?;
+
+    if !reverted {
+        tracing::warn!("Failed to revert the state of the node after the simulation");
+    }
+
+    let classified = result?;
+
+    let mut txn = database.pool().begin().await?;
+    for (token_id, tax_bps, rebasing) in &classified {
+        DB::set_token_transfer_flags(&mut txn, *token_id, *tax_bps, *rebasing).await?;
+    }
+    txn.commit().await?;
+
+    let taxed = classified
+        .iter()
+        .filter(|(_, tax_bps, _)| *tax_bps > 0)
+        .count();
+    let rebasing = classified
+        .iter()
+        .filter(|(_, _, rebasing)| *rebasing)
+        .count();
+
+    println!(
+        "Classified {} of {} tokens: {} with transfer tax, {} rebasing",
+        classified.len(),
+        tokens.len(),
+        taxed,
+        rebasing,
+    );
+
+    Ok(())
+}
+
+/// Return the address of the pair with the largest reserve of each token.
+async fn deepest_pairs(conn: &mut PgConnection) -> eyre::Result<HashMap<i32, Address>> {
+    let mut deepest: HashMap<i32, (BigDecimal, Address)> = HashMap::new();
+
+    let mut pairs_stream = DB::pairs_stream(conn, None).await?;
+    while let Some(result) = pairs_stream.next().await {
+        let pair = result?;
+        let Ok(address) = pair.pair_address.trim_end().parse::<Address>() else {
+            continue;
+        };
+
+        for (token, reserve) in [
+            (pair.token0_id, pair.reserve0),
+            (pair.token1_id, pair.reserve1),
+        ] {
+            match deepest.get(&token) {
+                Some((deepest_reserve, _)) if *deepest_reserve >= reserve => {}
+                _ => {
+                    deepest.insert(token, (reserve, address));
+                }
+            }
+        }
+    }
+
+    Ok(deepest
+        .into_iter()
+        .map(|(token, (_, address))| (token, address))
+        .collect())
+}
+
+/// Return the transfer tax and whether the token is rebasing for the tokens
+/// which could be transferred out of their pair.
+async fn probe_tokens(
+    client: &Arc<Provider<Http>>,
+    tokens: &[TokenEntry],
+    pairs: &HashMap<i32, Address>,
+) -> eyre::Result<Vec<(i32, u32, bool)>> {
+    let mut probes = Vec::new();
+
+    for token in tokens {
+        let Some(pair) = pairs.get(&token.id) else {
+            continue;
+        };
+        let Ok(address) = token.address.trim_end().parse::<Address>() else {
+            tracing::warn!("Skipped token with malformed address {}", token.address);
+            continue;
+        };
+
+        match probe_transfers(client, token.id, address, *pair).await {
+            Ok(probe) => probes.push(probe),
+            Err(err) => tracing::warn!(token = token.id, "Failed to simulate transfers: {:?}", err),
+        }
+    }
+
+    client
+        .request::<_, serde_json::Value>("evm_increaseTime", [REBASE_PERIOD])
+        .await?;
+    client
+        .request::<_, serde_json::Value>("evm_mine", ())
+        .await?;
+
+    let mut classified = Vec::with_capacity(probes.len());
+    for probe in probes {
+        let balance = probe.contract.balance_of(probe.holder).call().await?;
+
+        classified.push((probe.token_id, probe.tax_bps, balance != probe.kept));
+    }
+
+    Ok(classified)
+}
+
+async fn probe_transfers(
+    client: &Arc<Provider<Http>>,
+    token_id: i32,
+    token: Address,
+    pair: Address,
+) -> eyre::Result<TransferProbe> {
+    let contract = IUniswapV2ERC20::new(token, client.clone());
+
+    // Fresh account per token, so the balances of different tokens don't
+    // interfere.
+    let holder = Address::from_slice(&keccak256(token.as_bytes())[12..]);
+
+    let amount = contract.balance_of(pair).call().await? / TRANSFER_DIVISOR;
+    if amount.is_zero() {
+        eyre::bail!("Pair {:?} holds too little of the token", pair);
+    }
+
+    let received = transfer(client, &contract, pair, holder, amount)
+        .await?
+        .ok_or_else(|| eyre::eyre!("Transfer out of pair {:?} reverted", pair))?;
+
+    // The rest is kept to find if the balance changes by itself.
+    let sent_back = received / 2;
+    let sell_tax_bps = match transfer(client, &contract, holder, pair, sent_back).await? {
+        Some(returned) if !sent_back.is_zero() => tax_bps(sent_back, returned),
+        // Tokens which can't be sold lose everything.
+        _ => TAX_DENOMINATOR,
+    };
+
+    let kept = contract.balance_of(holder).call().await?;
+
+    Ok(TransferProbe {
+        token_id,
+        contract,
+        holder,
+        kept,
+        tax_bps: tax_bps(amount, received).max(sell_tax_bps),
+    })
+}
+
+/// Transfer the amount from the impersonated account, return by how much
+/// the balance of the recipient increased or `None` if the transfer reverted.
+async fn transfer(
+    client: &Provider<Http>,
+    contract: &IUniswapV2ERC20<Provider<Http>>,
+    from: Address,
+    to: Address,
+    amount: U256,
+) -> eyre::Result<Option<U256>> {
+    client
+        .request::<_, ()>("anvil_impersonateAccount", [from])
+        .await?;
+    // Pairs have no ether to pay for the gas.
+    client
+        .request::<_, ()>("anvil_setBalance", (from, U256::exp10(18)))
+        .await?;
+
+    let balance_before = contract.balance_of(to).call().await?;
+
+    let call = contract.transfer(to, amount).from(from).gas(TRANSFER_GAS);
+    let receipt = call.send().await?.await?;
+
+    if receipt.and_then(|receipt| receipt.status) != Some(1.into()) {
+        return Ok(None);
+    }
+
+    let balance_after = contract.balance_of(to).call().await?;
+
+    Ok(Some(balance_after.saturating_sub(balance_before)))
+}
+
+/// Share of the sent amount which wasn't received, in basis points.
+fn tax_bps(sent: U256, received: U256) -> u32 {
+    if sent.is_zero() || received >= sent {
+        return 0;
+    }
+
+    ((sent - received) * U256::from(TAX_DENOMINATOR) / sent).as_u32()
+}
+
+#[cfg(test)]
+mod tests {
+    use bindings::erc20_mock::ERC20Mock;
+    use ethers::providers::Middleware;
+
+    use crate::testing;
+
+    use super::*;
+
+    #[test]
+    fn test_tax_bps() {
+        assert_eq!(tax_bps(U256::from(1000), U256::from(1000)), 0);
+        assert_eq!(tax_bps(U256::from(1000), U256::from(950)), 500);
+        assert_eq!(tax_bps(U256::from(1000), U256::zero()), TAX_DENOMINATOR);
+        // Rounded down, so dust lost to the rounding isn't a tax.
+        assert_eq!(tax_bps(U256::from(30_000), U256::from(29_999)), 0);
+        assert_eq!(tax_bps(U256::from(1000), U256::from(1001)), 0);
+        assert_eq!(tax_bps(U256::zero(), U256::zero()), 0);
+    }
+
+    /// Classifies a plain token held by a fake pair, which must have neither
+    /// the transfer tax nor the rebasing.
+    ///
+    /// Requires a local Anvil node at `ETH_RPC_URL`:
+    ///
+    /// ```sh
+    /// anvil & cargo test -p bootstrapper -- --ignored
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires a local Anvil node"]
+    async fn test_classifies_plain_token() -> eyre::Result<()> {
+        let provider = testing::anvil_provider()?;
+        let sender = provider.get_accounts().await?[0];
+        let client = Arc::new(provider.clone().with_sender(sender));
+        let provider = Arc::new(provider);
+
+        let token = ERC20Mock::deploy(
+            client.clone(),
+            ("Token".to_string(), "TKN".to_string(), 18u8),
+        )?
+        .send()
+        .await?;
+        let pair = Address::random();
+        token.mint(pair, U256::exp10(24)).send().await?.await?;
+
+        let entry = TokenEntry {
+            id: 1,
+            address: database::format_address(token.address()),
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            decimals: 18,
+            metadata_status: "complete".to_string(),
+            transfer_tax_bps: None,
+            rebasing: None,
+        };
+
+        let classified = probe_tokens(&provider, &[entry], &HashMap::from([(1, pair)])).await?;
+        assert_eq!(classified, vec![(1, 0, false)]);
+
+        Ok(())
+    }
+}
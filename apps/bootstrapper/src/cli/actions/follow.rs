@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use clap::Args;
+use color_eyre::eyre;
+use tokio_util::sync::CancellationToken;
+
+use crate::follower::{Follower, FollowerConfig};
+use config::Config;
+
+#[derive(Args, Debug)]
+pub struct FollowArgs {
+    /// URL to the Ethereum node.
+    #[arg(short = 'e', long = "eth-url")]
+    pub ethereum_provider_url: String,
+
+    /// Block to start following from, by default the block after the last
+    /// one the follower processed.
+    #[arg(short, long)]
+    pub from_block: Option<u64>,
+
+    /// Delay between polls of the new blocks in seconds.
+    #[arg(short, long, default_value = "12")]
+    pub poll_interval: u64,
+
+    /// Maximum number of blocks to request logs for at once.
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..), default_value = "1000")]
+    pub blocks_per_request: u64,
+}
+
+pub async fn follow(
+    config: Config,
+    FollowArgs {
+        ethereum_provider_url,
+        from_block,
+        poll_interval,
+        blocks_per_request,
+    }: FollowArgs,
+) -> eyre::Result<()> {
+    let ctrl_c = tokio::signal::ctrl_c();
+    let cancellation = CancellationToken::new();
+
+    let follower = Follower::try_from_config(
+        FollowerConfig {
+            db_url: config.database.url,
+            eth_url: ethereum_provider_url,
+            from_block,
+            poll_interval: Duration::from_secs(poll_interval),
+            blocks_per_request,
+        },
+        cancellation.clone(),
+    )
+    .await?;
+
+    tokio::select! {
+        _ = ctrl_c => cancellation.cancel(),
+        res = follower.run() => {
+            res?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bindings::{uniswap_v2_factory::UniswapV2Factory, uniswap_v2_pair::UniswapV2Pair};
+use clap::Args;
+use color_eyre::eyre;
+use database::{pairs::PairEntry, DB};
+use ethers::{
+    abi::Address,
+    providers::{Http, Provider},
+    types::U256,
+};
+use futures::{stream, StreamExt};
+
+use config::Config;
+
+use crate::fetcher::factory_addresses;
+
+#[derive(Args, Debug)]
+pub struct MigrateAddressesArgs {
+    /// URL to the Ethereum node.
+    #[arg(short = 'e', long = "eth-url")]
+    pub ethereum_provider_url: String,
+
+    /// The number of addresses to resolve concurrently.
+    #[arg(short, long, default_value = "1")]
+    pub workers: usize,
+}
+
+/// Store the full addresses of the factories, pairs and tokens indexed
+/// before the migration `003`, which stored them shortened to `0x1234…abcd`.
+///
+/// The shortened addresses can't be parsed back, so they are resolved on
+/// chain: factories by the configured ones, pairs by their number in the
+/// factory and tokens by the `token0` and `token1` of their pairs. Entries
+/// which are already indexed again with the full address are merged into
+/// the new ones.
+pub async fn migrate_addresses(
+    config: Config,
+    MigrateAddressesArgs {
+        ethereum_provider_url,
+        workers,
+    }: MigrateAddressesArgs,
+) -> eyre::Result<()> {
+    let database = DB::from_url(&config.database.url).await?;
+    let eth_client = Arc::new(Provider::<Http>::try_from(ethereum_provider_url.as_str())?);
+
+    let mut conn = database.pool().acquire().await?;
+    let factory_entries = DB::factories(&mut conn).await?;
+    let tokens = DB::tokens(&mut conn).await?;
+    drop(conn);
+    let pairs = database.pairs_after(0).await?;
+
+    let configured: Vec<Address> = config
+        .factories
+        .iter()
+        .map(|factory| factory.address)
+        .collect();
+    let factories = factory_addresses(&factory_entries, &configured);
+    let legacy_factories: Vec<(i32, Address)> = factory_entries
+        .iter()
+        .filter(|entry| database::is_legacy_address(&entry.address))
+        .filter_map(|entry| match factories.get(&entry.id) {
+            Some(address) => Some((entry.id, *address)),
+            None => {
+                tracing::warn!(
+                    "Factory {} isn't configured, its pairs are left as is",
+                    entry.address.trim_end()
+                );
+                None
+            }
+        })
+        .collect();
+
+    let legacy_pairs: Vec<&PairEntry> = pairs
+        .iter()
+        .filter(|pair| database::is_legacy_address(&pair.address))
+        .collect();
+
+    tracing::info!("Resolving addresses of {} pairs", legacy_pairs.len());
+
+    let resolved_pairs: Vec<eyre::Result<(i32, Address)>> = stream::iter(legacy_pairs)
+        .map(|pair| resolve_pair(&eth_client, &factories, pair))
+        .buffer_unordered(workers.max(1))
+        .collect()
+        .await;
+    let resolved_pairs = collect_resolved(resolved_pairs, "pair");
+
+    // Pairs indexed with the full address give the tokens too.
+    let mut pair_addresses: HashMap<i32, Address> = pairs
+        .iter()
+        .filter_map(|pair| Some((pair.id, pair.address.trim_end().parse().ok()?)))
+        .collect();
+    pair_addresses.extend(resolved_pairs.iter().copied());
+
+    let legacy_tokens: Vec<i32> = tokens
+        .iter()
+        .filter(|token| database::is_legacy_address(&token.address))
+        .map(|token| token.id)
+        .collect();
+    let stored_tokens: HashMap<i32, &str> = tokens
+        .iter()
+        .map(|token| (token.id, token.address.as_str()))
+        .collect();
+
+    tracing::info!("Resolving addresses of {} tokens", legacy_tokens.len());
+
+    let resolved_tokens: Vec<eyre::Result<(i32, Address)>> = stream::iter(legacy_tokens)
+        .map(|token_id| {
+            resolve_token(
+                &eth_client,
+                &pairs,
+                &pair_addresses,
+                token_id,
+                stored_tokens[&token_id],
+            )
+        })
+        .buffer_unordered(workers.max(1))
+        .collect()
+        .await;
+    let resolved_tokens = collect_resolved(resolved_tokens, "token");
+
+    let mut merged = 0;
+    let mut txn = database.pool().begin().await?;
+    for (factory_id, address) in &legacy_factories {
+        merged += DB::migrate_factory_address(&mut txn, *factory_id, *address).await? as usize;
+    }
+    for (token_id, address) in &resolved_tokens {
+        merged += DB::migrate_token_address(&mut txn, *token_id, *address).await? as usize;
+    }
+    for (pair_id, address) in &resolved_pairs {
+        merged += DB::migrate_pair_address(&mut txn, *pair_id, *address).await? as usize;
+    }
+    txn.commit().await?;
+
+    println!(
+        "Migrated addresses of {} factories, {} pairs and {} tokens, {} of them merged into entries indexed again",
+        legacy_factories.len(),
+        resolved_pairs.len(),
+        resolved_tokens.len(),
+        merged,
+    );
+
+    Ok(())
+}
+
+/// Return the address of the pair by its number in the factory.
+async fn resolve_pair(
+    eth_client: &Arc<Provider<Http>>,
+    factories: &HashMap<i32, Address>,
+    pair: &PairEntry,
+) -> eyre::Result<(i32, Address)> {
+    let factory = factories.get(&pair.factory).ok_or_else(|| {
+        eyre::eyre!(
+            "Factory {} of pair {} isn't configured",
+            pair.factory,
+            pair.id
+        )
+    })?;
+
+    let factory_contract = UniswapV2Factory::new(*factory, eth_client.clone());
+    let address = factory_contract
+        .all_pairs(U256::from(pair.number))
+        .call()
+        .await?;
+
+    check_resolved(&pair.address, address)?;
+
+    Ok((pair.id, address))
+}
+
+/// Return the address of the token by any of its pairs.
+async fn resolve_token(
+    eth_client: &Arc<Provider<Http>>,
+    pairs: &[PairEntry],
+    pair_addresses: &HashMap<i32, Address>,
+    token_id: i32,
+    stored: &str,
+) -> eyre::Result<(i32, Address)> {
+    let (pair, pair_address) = pairs
+        .iter()
+        .filter(|pair| pair.token0 == token_id || pair.token1 == token_id)
+        .find_map(|pair| Some((pair, *pair_addresses.get(&pair.id)?)))
+        .ok_or_else(|| eyre::eyre!("Token {} has no pairs with known address", token_id))?;
+
+    let pair_contract = UniswapV2Pair::new(pair_address, eth_client.clone());
+    let address = if pair.token0 == token_id {
+        pair_contract.token_0().call().await?
+    } else {
+        pair_contract.token_1().call().await?
+    };
+
+    check_resolved(stored, address)?;
+
+    Ok((token_id, address))
+}
+
+/// Return an error if the resolved address isn't the stored one.
+fn check_resolved(stored: &str, address: Address) -> eyre::Result<()> {
+    if !database::matches_address(stored, address) {
+        eyre::bail!(
+            "Resolved address {:?} doesn't match the stored {}",
+            address,
+            stored.trim_end()
+        );
+    }
+
+    Ok(())
+}
+
+/// Return the resolved addresses, logging the entries which failed.
+fn collect_resolved(results: Vec<eyre::Result<(i32, Address)>>, kind: &str) -> Vec<(i32, Address)> {
+    let mut resolved = Vec::with_capacity(results.len());
+    for result in results {
+        match result {
+            Ok(entry) => resolved.push(entry),
+            Err(err) => tracing::warn!("Failed to resolve {} address: {:?}", kind, err),
+        }
+    }
+
+    resolved
+}
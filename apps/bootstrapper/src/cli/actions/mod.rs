@@ -1 +1,7 @@
+pub(crate) mod classify;
+pub(crate) mod follow;
+pub(crate) mod migrate_addresses;
+pub(crate) mod probe_fee;
+pub(crate) mod repair_reserves;
 pub(crate) mod run;
+pub(crate) mod snapshot;
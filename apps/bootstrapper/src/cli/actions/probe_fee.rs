@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use bindings::uniswap_v2_pair::{SwapFilter, SyncFilter, UniswapV2Pair, UniswapV2PairEvents};
+use clap::Args;
+use color_eyre::eyre;
+use database::DB;
+use ethers::{
+    abi::Address,
+    contract::LogMeta,
+    providers::{Http, Middleware, Provider},
+    types::U256,
+};
+
+use config::Config;
+
+/// Denominator of the derived fee, gives the precision of 0.01%.
+const FEE_DENOMINATOR: u32 = 10_000;
+
+#[derive(Args, Debug)]
+pub struct ProbeFeeArgs {
+    /// URL to the Ethereum node.
+    #[arg(short = 'e', long = "eth-url")]
+    pub ethereum_provider_url: String,
+
+    /// The address of the pair to derive the fee of.
+    #[arg(short, long)]
+    pub pair: Address,
+
+    /// Number of the latest blocks to look for swaps in.
+    #[arg(short, long, default_value = "10000")]
+    pub blocks: u64,
+
+    /// Store the derived fee as the fee of the pair.
+    #[arg(short, long)]
+    pub save: bool,
+}
+
+/// Derive the swap fee of the pair from the swaps observed on chain, for the
+/// pairs which fee isn't known.
+///
+/// Each `Swap` event is preceded by the `Sync` event with reserves after the
+/// swap, which gives the reserves before it, so the fee could be found from
+/// the constant product formula. The median over the swaps is used, as
+/// swaps with fee-on-transfer tokens or custom routers could distort some.
+pub async fn probe_fee(
+    config: Config,
+    ProbeFeeArgs {
+        ethereum_provider_url,
+        pair,
+        blocks,
+        save,
+    }: ProbeFeeArgs,
+) -> eyre::Result<()> {
+    let eth_client = Arc::new(Provider::<Http>::try_from(ethereum_provider_url.as_str())?);
+
+    let best_block = eth_client.get_block_number().await?.as_u64();
+    let from_block = best_block.saturating_sub(blocks);
+
+    let pair_contract = UniswapV2Pair::new(pair, eth_client);
+    let events = pair_contract
+        .events()
+        .from_block(from_block)
+        .to_block(best_block)
+        .query_with_meta()
+        .await?;
+
+    let mut fees = Vec::new();
+    let mut last_sync: Option<(SyncFilter, LogMeta)> = None;
+
+    for (event, meta) in events {
+        match event {
+            UniswapV2PairEvents::SyncFilter(sync) => last_sync = Some((sync, meta)),
+            UniswapV2PairEvents::SwapFilter(swap) => {
+                let Some((sync, sync_meta)) = last_sync.take() else {
+                    continue;
+                };
+
+                let is_same_swap = sync_meta.transaction_hash == meta.transaction_hash
+                    && sync_meta.log_index + 1 == meta.log_index;
+
+                if let Some(fee) = is_same_swap.then(|| derive_fee(&swap, &sync)).flatten() {
+                    fees.push(fee);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if fees.is_empty() {
+        eyre::bail!("No swaps of the pair found in the last {} blocks", blocks);
+    }
+
+    fees.sort_unstable();
+    let numerator = fees[fees.len() / 2];
+
+    println!(
+        "Derived fee of {:?} from {} swaps: {}/{} ({:.2}%)",
+        pair,
+        fees.len(),
+        numerator,
+        FEE_DENOMINATOR,
+        (FEE_DENOMINATOR - numerator) as f64 / FEE_DENOMINATOR as f64 * 100.0,
+    );
+
+    if save {
+        let database = DB::from_url(&config.database.url).await?;
+        let mut conn = database.pool().acquire().await?;
+
+        if !DB::set_pair_fee(&mut conn, pair, numerator, FEE_DENOMINATOR).await? {
+            eyre::bail!("Pair {:?} isn't indexed", pair);
+        }
+    }
+
+    Ok(())
+}
+
+/// Derive the fraction of the input amount left after the fee, multiplied by
+/// [`FEE_DENOMINATOR`], from the swap and the reserves after it.
+///
+/// From the constant product formula:
+///
+/// $$
+/// \text{fee} = \frac{\text{amountOut} \times \text{reserveIn}}{\text{amountIn} \times (\text{reserveOut} - \text{amountOut})}
+/// $$
+///
+/// Return `None` for the swaps with input in both tokens, as it's not clear
+/// which part of the input was swapped.
+fn derive_fee(swap: &SwapFilter, sync: &SyncFilter) -> Option<u32> {
+    let reserve0 = U256::from(sync.reserve_0) + swap.amount_0_out;
+    let reserve1 = U256::from(sync.reserve_1) + swap.amount_1_out;
+
+    let (amount_in, amount_out, reserve_in, reserve_out) =
+        match (swap.amount_0_in.is_zero(), swap.amount_1_in.is_zero()) {
+            (false, true) => (
+                swap.amount_0_in,
+                swap.amount_1_out,
+                reserve0.checked_sub(swap.amount_0_in)?,
+                reserve1,
+            ),
+            (true, false) => (
+                swap.amount_1_in,
+                swap.amount_0_out,
+                reserve1.checked_sub(swap.amount_1_in)?,
+                reserve0,
+            ),
+            _ => return None,
+        };
+
+    let denominator = amount_in.checked_mul(reserve_out.checked_sub(amount_out)?)?;
+    if denominator.is_zero() || amount_out.is_zero() {
+        return None;
+    }
+
+    // Rounded to the nearest, as the output is floored on chain.
+    let two = U256::from(2);
+    let numerator = amount_out * reserve_in * U256::from(FEE_DENOMINATOR) * two + denominator;
+    let fee = numerator / (denominator * two);
+
+    (fee <= U256::from(FEE_DENOMINATOR)).then(|| fee.as_u32())
+}
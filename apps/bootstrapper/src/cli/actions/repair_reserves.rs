@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use bindings::{uniswap_v2_factory::UniswapV2Factory, uniswap_v2_pair::UniswapV2Pair};
+use clap::Args;
+use color_eyre::eyre;
+use database::{reserves::SuspiciousReserveEntry, DB};
+use ethers::{
+    abi::Address,
+    providers::{Http, Provider},
+    types::U256,
+};
+use futures::{stream, StreamExt};
+
+use config::Config;
+
+use crate::fetcher::factory_addresses;
+
+#[derive(Args, Debug)]
+pub struct RepairReservesArgs {
+    /// URL to the Ethereum node, should be an archive one to fetch reserves
+    /// at old blocks.
+    #[arg(short = 'e', long = "eth-url")]
+    pub ethereum_provider_url: String,
+
+    /// Fetch again all the reserves, not only the negative ones. Reserves
+    /// above `u64::MAX` could have been truncated to a positive value.
+    #[arg(short, long)]
+    pub all: bool,
+
+    /// The number of entries to repair concurrently.
+    #[arg(short, long, default_value = "1")]
+    pub workers: usize,
+
+    /// Address of a factory which isn't configured anymore, its pairs can't
+    /// be found otherwise. Can be repeated.
+    #[arg(long = "factory")]
+    pub factories: Vec<Address>,
+}
+
+/// Fetch again reserves which could have been truncated when they were stored
+/// as i64, at the same blocks they were fetched on.
+///
+/// Pairs are found by their number in the factory, as the stored address
+/// could be shortened by older versions of the indexer.
+pub async fn repair_reserves(
+    config: Config,
+    RepairReservesArgs {
+        ethereum_provider_url,
+        all,
+        workers,
+        factories,
+    }: RepairReservesArgs,
+) -> eyre::Result<()> {
+    let database = DB::from_url(&config.database.url).await?;
+    let eth_client = Arc::new(Provider::<Http>::try_from(ethereum_provider_url.as_str())?);
+
+    let entries = database.suspicious_reserves(all).await?;
+
+    tracing::info!("Found {} reserves entries to repair", entries.len());
+
+    let mut known_factories: Vec<Address> = config
+        .factories
+        .iter()
+        .map(|factory| factory.address)
+        .collect();
+    known_factories.extend(factories);
+
+    let mut conn = database.pool().acquire().await?;
+    let factories = factory_addresses(&DB::factories(&mut conn).await?, &known_factories);
+    drop(conn);
+
+    let pairs = pairs_in_factories(&entries, &factories)?;
+    let pair_addresses: Vec<eyre::Result<(i32, Address)>> = stream::iter(pairs)
+        .map(|(pair_id, (factory, number))| {
+            resolve_pair(eth_client.clone(), pair_id, factory, number)
+        })
+        .buffer_unordered(workers.max(1))
+        .collect()
+        .await;
+    let pair_addresses: HashMap<i32, Address> =
+        pair_addresses.into_iter().collect::<eyre::Result<_>>()?;
+
+    let results: Vec<eyre::Result<()>> = stream::iter(entries)
+        .map(|entry| {
+            repair_entry(
+                &database,
+                eth_client.clone(),
+                pair_addresses[&entry.pair_id],
+                entry,
+            )
+        })
+        .buffer_unordered(workers.max(1))
+        .collect()
+        .await;
+
+    let failed = results
+        .into_iter()
+        .filter_map(Result::err)
+        .inspect(|err| tracing::warn!("Failed to repair reserves: {:?}", err))
+        .count();
+
+    if failed > 0 {
+        eyre::bail!("Failed to repair {} reserves entries", failed);
+    }
+
+    tracing::info!("Reserves are repaired");
+
+    Ok(())
+}
+
+/// Return the factory address and the number of the pair of each entry by
+/// the pair ids.
+fn pairs_in_factories(
+    entries: &[SuspiciousReserveEntry],
+    factories: &HashMap<i32, Address>,
+) -> eyre::Result<BTreeMap<i32, (Address, u64)>> {
+    let mut pairs = BTreeMap::new();
+    for entry in entries {
+        let factory = factories.get(&entry.factory_id).ok_or_else(|| {
+            eyre::eyre!(
+                "Factory {} of pair {} is unknown, pass its address with --factory",
+                entry.factory_id,
+                entry.pair_address.trim_end(),
+            )
+        })?;
+
+        pairs.insert(entry.pair_id, (*factory, entry.pair_number as u64));
+    }
+
+    Ok(pairs)
+}
+
+/// Return the address of the pair by its number in the factory.
+async fn resolve_pair(
+    eth_client: Arc<Provider<Http>>,
+    pair_id: i32,
+    factory: Address,
+    number: u64,
+) -> eyre::Result<(i32, Address)> {
+    let factory_contract = UniswapV2Factory::new(factory, eth_client);
+    let address = factory_contract
+        .all_pairs(U256::from(number))
+        .call()
+        .await?;
+
+    Ok((pair_id, address))
+}
+
+async fn repair_entry(
+    database: &DB,
+    eth_client: Arc<Provider<Http>>,
+    pair_address: Address,
+    entry: SuspiciousReserveEntry,
+) -> eyre::Result<()> {
+    let pair_contract = UniswapV2Pair::new(pair_address, eth_client);
+    let (reserve0, reserve1, _) = pair_contract
+        .get_reserves()
+        .block(entry.block_height as u64)
+        .call()
+        .await?;
+
+    let mut conn = database.pool().acquire().await?;
+    DB::update_reserves(&mut conn, entry.id, reserve0, reserve1).await?;
+
+    tracing::debug!(id = entry.id, "Repaired reserves");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use database::{factories::FactoryEntry, format_legacy_address};
+
+    use super::*;
+
+    fn factory_entry(id: i32, address: String) -> FactoryEntry {
+        FactoryEntry {
+            id,
+            // Stored as `CHAR(42)`, so padded with spaces.
+            address: format!("{:<42}", address),
+            name: None,
+            fee_numerator: 997,
+            fee_denominator: 1000,
+        }
+    }
+
+    fn reserve_entry(
+        id: i32,
+        pair_id: i32,
+        pair_address: String,
+        factory_id: i32,
+        pair_number: i32,
+    ) -> SuspiciousReserveEntry {
+        SuspiciousReserveEntry {
+            id,
+            pair_id,
+            pair_address: format!("{:<42}", pair_address),
+            factory_id,
+            pair_number,
+            block_height: 10_000_000,
+        }
+    }
+
+    #[test]
+    fn test_legacy_pairs_are_found_in_factory() {
+        let factory = Address::from_low_u64_be(0xfac7);
+        let other_factory = Address::from_low_u64_be(0xfac8);
+        let pair = Address::from_low_u64_be(0xbeef);
+
+        let stored_factories = [
+            factory_entry(1, format_legacy_address(factory)),
+            factory_entry(2, format_legacy_address(other_factory)),
+        ];
+        let factories = factory_addresses(&stored_factories, &[factory]);
+        assert_eq!(factories, HashMap::from([(1, factory)]));
+
+        let entries = [
+            reserve_entry(1, 7, format_legacy_address(pair), 1, 42),
+            reserve_entry(2, 7, format_legacy_address(pair), 1, 42),
+        ];
+        let pairs = pairs_in_factories(&entries, &factories).unwrap();
+        assert_eq!(pairs, BTreeMap::from([(7, (factory, 42))]));
+
+        // The factory which isn't known can't be matched by the shortened
+        // address.
+        let entries = [reserve_entry(3, 8, format_legacy_address(pair), 2, 0)];
+        assert!(pairs_in_factories(&entries, &factories).is_err());
+
+        let factories = factory_addresses(&stored_factories, &[factory, other_factory]);
+        let pairs = pairs_in_factories(&entries, &factories).unwrap();
+        assert_eq!(pairs, BTreeMap::from([(8, (other_factory, 0))]));
+    }
+}
@@ -1,24 +1,53 @@
-use clap::Args;
+use clap::{Args, ValueEnum};
 use color_eyre::eyre;
 use ethers::types::Address;
 use tokio_util::sync::CancellationToken;
 
-use crate::fetcher::{IndexerConfig, IndexerPool};
-use config::Config;
+use crate::fetcher::{Discovery, IndexerConfig, IndexerPool};
+use config::{Config, Factory};
 
 #[derive(Args, Debug)]
 pub struct RunArgs {
-    /// The address of the factory contract.
+    /// The address of the factory contract to index instead of the ones
+    /// from the config.
     #[arg(short, long)]
-    pub factory_address: Address,
+    pub factory_address: Option<Address>,
 
     /// URL to the Ethereum node.
     #[arg(short = 'e', long = "eth-url")]
     pub ethereum_provider_url: String,
 
-    /// The number of workers to spawn.
-    #[arg(short, long, default_value = "1")]
+    /// The number of workers to spawn. Calls of the workers made at the same
+    /// time are batched together, so the workers mostly wait for the batches
+    /// and many of them are needed to fill the batches.
+    #[arg(short, long, default_value = "64")]
     pub workers: u32,
+
+    /// Maximum number of calls batched into a single Multicall3 request.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value = "500")]
+    pub batch_size: u64,
+
+    /// The way new pairs are discovered.
+    #[arg(short, long, value_enum, default_value_t = DiscoveryMode::Logs)]
+    pub discovery: DiscoveryMode,
+
+    /// Block to start scanning `PairCreated` events from, usually the block
+    /// the factory was deployed in.
+    #[arg(long, default_value = "0")]
+    pub from_block: u64,
+
+    /// Maximum number of blocks to request `PairCreated` events for at once.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value = "10000")]
+    pub blocks_per_request: u64,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DiscoveryMode {
+    /// Scan `PairCreated` events of the factory, falling back to `index` if
+    /// the node refuses to return them.
+    Logs,
+    /// Request pairs one by one by their index in the factory.
+    Index,
 }
 
 pub async fn run(
@@ -27,8 +56,31 @@ pub async fn run(
         factory_address,
         ethereum_provider_url,
         workers,
+        batch_size,
+        discovery,
+        from_block,
+        blocks_per_request,
     }: RunArgs,
 ) -> eyre::Result<()> {
+    let factories = match factory_address {
+        Some(address) => vec![config
+            .factories
+            .iter()
+            .find(|factory| factory.address == address)
+            .cloned()
+            .unwrap_or_else(|| Factory {
+                name: database::format_address(address),
+                address,
+                fee: Default::default(),
+                router: None,
+            })],
+        None => config.factories,
+    };
+
+    if factories.is_empty() {
+        eyre::bail!("No factories to index, specify them in the config or with --factory-address");
+    }
+
     let ctrl_c = tokio::signal::ctrl_c();
     let cancellation = CancellationToken::new();
 
@@ -36,9 +88,18 @@ pub async fn run(
         IndexerConfig {
             db_url: config.database.url,
             eth_url: ethereum_provider_url,
-            factory_address,
+            factories,
+            pair_fees: config.fees,
             // TODO: make this configurable
             concurrency: workers as usize,
+            batch_size: batch_size as usize,
+            discovery: match discovery {
+                DiscoveryMode::Logs => Discovery::Logs {
+                    from_block,
+                    blocks_per_request,
+                },
+                DiscoveryMode::Index => Discovery::Index,
+            },
         },
         cancellation.clone(),
     )
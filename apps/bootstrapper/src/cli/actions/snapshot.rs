@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use bindings::uniswap_v2_pair::UniswapV2Pair;
+use clap::Args;
+use color_eyre::eyre;
+use database::{pairs::PairEntry, DB};
+use ethers::{
+    abi::Address,
+    contract::ContractError,
+    providers::{Http, Middleware, Provider},
+};
+use futures::{stream, StreamExt};
+
+use config::Config;
+
+#[derive(Args, Debug)]
+pub struct SnapshotArgs {
+    /// URL to the Ethereum node, should be an archive one to fetch reserves
+    /// at old blocks.
+    #[arg(short = 'e', long = "eth-url")]
+    pub ethereum_provider_url: String,
+
+    /// Height of the block to fetch the reserves at.
+    #[arg(short, long)]
+    pub block: u64,
+
+    /// The number of pairs to fetch concurrently.
+    #[arg(short, long, default_value = "1")]
+    pub workers: usize,
+}
+
+/// Outcome of fetching the reserves of a single pair.
+enum Fetched {
+    Stored,
+
+    /// The pair wasn't deployed yet at the block.
+    Missing,
+}
+
+/// Fetch reserves of all the known pairs at the given block, so the graph
+/// could be built as it was at that block.
+pub async fn snapshot(
+    config: Config,
+    SnapshotArgs {
+        ethereum_provider_url,
+        block,
+        workers,
+    }: SnapshotArgs,
+) -> eyre::Result<()> {
+    let database = DB::from_url(&config.database.url).await?;
+    let eth_client = Arc::new(Provider::<Http>::try_from(ethereum_provider_url.as_str())?);
+
+    let block_entry = eth_client
+        .get_block(block)
+        .await?
+        .ok_or_else(|| eyre::eyre!("Block {} not found", block))?;
+    let block_id = database.insert_block(block_entry).await?;
+
+    // Pairs created after the block are known to be missing.
+    let pairs: Vec<PairEntry> = database
+        .pairs_after(0)
+        .await?
+        .into_iter()
+        .filter(|pair| {
+            pair.created_at_block
+                .map_or(true, |created| created <= block as i64)
+        })
+        .collect();
+
+    tracing::info!(
+        "Fetching reserves of {} pairs at block {}",
+        pairs.len(),
+        block
+    );
+
+    let results: Vec<eyre::Result<Fetched>> = stream::iter(pairs)
+        .map(|pair| fetch_pair_reserves(&database, eth_client.clone(), pair, block, block_id))
+        .buffer_unordered(workers.max(1))
+        .collect()
+        .await;
+
+    let mut stored = 0;
+    let mut missing = 0;
+    let mut failed = 0;
+    for result in results {
+        match result {
+            Ok(Fetched::Stored) => stored += 1,
+            Ok(Fetched::Missing) => missing += 1,
+            Err(err) => {
+                tracing::warn!("Failed to fetch reserves: {:?}", err);
+                failed += 1;
+            }
+        }
+    }
+
+    tracing::info!(
+        "Stored reserves of {} pairs at block {}, {} pairs weren't deployed yet",
+        stored,
+        block,
+        missing,
+    );
+
+    if failed > 0 {
+        eyre::bail!(
+            "Failed to fetch reserves of {} pairs, the snapshot is incomplete",
+            failed
+        );
+    }
+
+    Ok(())
+}
+
+async fn fetch_pair_reserves(
+    database: &DB,
+    eth_client: Arc<Provider<Http>>,
+    pair: PairEntry,
+    block: u64,
+    block_id: i32,
+) -> eyre::Result<Fetched> {
+    let pair_address: Address = pair
+        .address
+        .trim_end()
+        .parse()
+        .map_err(|_| eyre::eyre!("Malformed pair address: {}", pair.address))?;
+
+    let pair_contract = UniswapV2Pair::new(pair_address, eth_client);
+    let reserves = pair_contract.get_reserves().block(block).call().await;
+
+    let (reserve0, reserve1) = match reserves {
+        Ok((reserve0, reserve1, _)) => (reserve0, reserve1),
+        // Calls to an address without code return no data, which fails to be
+        // decoded, while node errors are reported as middleware ones.
+        Err(ContractError::MiddlewareError { e }) => return Err(e.into()),
+        Err(ContractError::ProviderError { e }) => return Err(e.into()),
+        Err(err) => {
+            tracing::debug!(pair = pair.id, "No reserves at block {}: {}", block, err);
+            return Ok(Fetched::Missing);
+        }
+    };
+
+    let mut conn = database.pool().acquire().await?;
+    DB::insert_reserves(&mut conn, pair.id, reserve0, reserve1, block_id).await?;
+
+    Ok(Fetched::Stored)
+}
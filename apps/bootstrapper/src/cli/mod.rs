@@ -6,7 +6,11 @@ use tracing::level_filters::LevelFilter;
 
 use config::Config;
 
-use self::actions::run::RunArgs;
+use self::actions::{
+    classify::ClassifyArgs, follow::FollowArgs, migrate_addresses::MigrateAddressesArgs,
+    probe_fee::ProbeFeeArgs, repair_reserves::RepairReservesArgs, run::RunArgs,
+    snapshot::SnapshotArgs,
+};
 
 mod actions;
 
@@ -49,6 +53,24 @@ impl Cli {
             Commands::Run(args) => {
                 actions::run::run(config, args).await?;
             }
+            Commands::Follow(args) => {
+                actions::follow::follow(config, args).await?;
+            }
+            Commands::RepairReserves(args) => {
+                actions::repair_reserves::repair_reserves(config, args).await?;
+            }
+            Commands::ProbeFee(args) => {
+                actions::probe_fee::probe_fee(config, args).await?;
+            }
+            Commands::Snapshot(args) => {
+                actions::snapshot::snapshot(config, args).await?;
+            }
+            Commands::Classify(args) => {
+                actions::classify::classify(config, args).await?;
+            }
+            Commands::MigrateAddresses(args) => {
+                actions::migrate_addresses::migrate_addresses(config, args).await?;
+            }
         }
 
         Ok(())
@@ -59,4 +81,27 @@ impl Cli {
 pub enum Commands {
     /// Run the full node.
     Run(RunArgs),
+
+    /// Follow `Sync` events of the indexed pairs and keep their reserves up
+    /// to date.
+    Follow(FollowArgs),
+
+    /// Fetch again reserves which could have been truncated by older
+    /// versions of the indexer.
+    RepairReserves(RepairReservesArgs),
+
+    /// Derive the swap fee of the pair from the swaps observed on chain.
+    ProbeFee(ProbeFeeArgs),
+
+    /// Fetch reserves of all the known pairs at a historical block, to route
+    /// against the graph as it was then.
+    Snapshot(SnapshotArgs),
+
+    /// Find fee-on-transfer and rebasing tokens by simulating transfers on
+    /// a forked Anvil node.
+    Classify(ClassifyArgs),
+
+    /// Store the full addresses of the entries indexed when they were
+    /// stored shortened to `0x1234…abcd`.
+    MigrateAddresses(MigrateAddressesArgs),
 }
@@ -1,23 +1,25 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use bindings::{
     i_uniswap_v2erc20::IUniswapV2ERC20, uniswap_v2_factory::UniswapV2Factory,
     uniswap_v2_pair::UniswapV2Pair,
 };
 use color_eyre::eyre;
-use database::DB;
+use config::{Factory, PairFee};
+use database::{factories::FactoryEntry, pairs::PairEntry, tokens::MetadataStatus, DB};
 use ethers::{
     abi::Address,
-    contract::Multicall,
     providers::{Http, Middleware, Provider},
     types::H160,
 };
 use futures::future;
 use sqlx::PgConnection;
 use tokio::select;
-use tokio_util::{task::TaskTracker, sync::CancellationToken};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tracing::instrument;
 
+use crate::{batcher::CallBatcher, metadata};
+
 /// Indexer fetches from chain data about pairs, tokens and reserves.
 pub struct IndexerPool {
     /// Database connector to store indexing results
@@ -26,8 +28,11 @@ pub struct IndexerPool {
     /// Client to interact with the Ethereum network
     eth_client: Arc<Provider<Http>>,
 
-    /// The factory contract provider
-    factory_contract: UniswapV2Factory<Provider<Http>>,
+    /// Factories to index
+    factories: Vec<Factory>,
+
+    /// Known fees of the pairs overriding the factory ones
+    pair_fees: Vec<PairFee>,
 
     /// Cancellation token to stop the indexer
     cancellation: CancellationToken,
@@ -37,35 +42,74 @@ pub struct IndexerPool {
 
     /// Sender of the pair nums to the workers
     tx: flume::Sender<Task>,
+
+    /// The way new pairs are discovered
+    discovery: Discovery,
 }
 
 pub struct IndexerConfig {
     pub db_url: String,
     pub eth_url: String,
-    pub factory_address: Address,
+    pub factories: Vec<Factory>,
+
+    /// Known fees of the pairs overriding the factory ones.
+    pub pair_fees: Vec<PairFee>,
 
     /// Number of concurrent workers to process pairs.
     pub concurrency: usize,
+
+    /// Maximum number of calls sent in a single Multicall3 request.
+    pub batch_size: usize,
+
+    /// The way new pairs are discovered.
+    pub discovery: Discovery,
+}
+
+/// The way new pairs of the factory are discovered.
+#[derive(Debug, Clone, Copy)]
+pub enum Discovery {
+    /// Scan `PairCreated` events of the factory by ranges of blocks, which
+    /// also gives the block the pair was created in.
+    Logs {
+        /// Block to start scanning from, if no pairs with known creation
+        /// block are indexed yet.
+        from_block: u64,
+
+        /// Maximum number of blocks to request logs for at once.
+        blocks_per_request: u64,
+    },
+    /// Request pairs one by one by their index in the factory.
+    Index,
 }
 
 impl IndexerPool {
-    pub async fn try_from_config(config: IndexerConfig, cancellation: CancellationToken) -> eyre::Result<Self> {
+    pub async fn try_from_config(
+        config: IndexerConfig,
+        cancellation: CancellationToken,
+    ) -> eyre::Result<Self> {
         let database = DB::from_url(&config.db_url).await?;
         let eth_client = Arc::new(Provider::<Http>::try_from(config.eth_url.as_str())?);
 
+        CallBatcher::check_deployed(&eth_client).await?;
+
         Ok(Self::new(database, eth_client, config, cancellation))
     }
 
-    pub fn new(database: DB, eth_client: Arc<Provider<Http>>, config: IndexerConfig, cancellation: CancellationToken) -> Self {
-        let factory_contract = UniswapV2Factory::new(config.factory_address, eth_client.clone());
+    pub fn new(
+        database: DB,
+        eth_client: Arc<Provider<Http>>,
+        config: IndexerConfig,
+        cancellation: CancellationToken,
+    ) -> Self {
         let (tx, rx) = flume::bounded(config.concurrency);
         let tracker = TaskTracker::new();
+        let batcher = CallBatcher::spawn(eth_client.clone(), config.batch_size);
 
         for _i in 0..config.concurrency {
             let worker = Worker::new(
                 database.clone(),
                 eth_client.clone(),
-                factory_contract.clone(),
+                batcher.clone(),
                 cancellation.child_token(),
                 rx.clone(),
             );
@@ -80,10 +124,12 @@ impl IndexerPool {
         Self {
             database,
             eth_client,
-            factory_contract,
+            factories: config.factories,
+            pair_fees: config.pair_fees,
             cancellation,
             tracker,
             tx,
+            discovery: config.discovery,
         }
     }
 
@@ -91,49 +137,379 @@ impl IndexerPool {
         let best_block_number = self.eth_client.get_block_number().await?;
         let best_block = self.eth_client.get_block(best_block_number).await?.unwrap();
 
+        let orphaned_pairs = rollback_orphaned_blocks(&self.database, &self.eth_client).await?;
+
         let block_id = self.database.insert_block(best_block).await?;
+
+        // Pairs are found by their number, as the stored address could be
+        // shortened by older versions of the indexer.
+        let mut conn = self.database.pool().acquire().await?;
+        let configured: Vec<Address> = self
+            .factories
+            .iter()
+            .map(|factory| factory.address)
+            .collect();
+        let factory_addresses = factory_addresses(&DB::factories(&mut conn).await?, &configured);
+        drop(conn);
+
+        for pair in orphaned_pairs {
+            let Some(factory_address) = factory_addresses.get(&pair.factory).copied() else {
+                tracing::warn!(
+                    "Skipped pair {} of the factory which isn't configured",
+                    pair.address.trim_end()
+                );
+                continue;
+            };
+
+            select! {
+                _ = self.cancellation.cancelled() => break,
+                _ = self.tx.send_async(Task::Reserves {
+                    pair_id: pair.id,
+                    factory_address,
+                    block_id,
+                    pair_num: pair.number as u64,
+                }) => {}
+            }
+        }
+
+        for factory in &self.factories {
+            if self.cancellation.is_cancelled() {
+                break;
+            }
+
+            self.index_factory(factory, block_id, best_block_number.as_u64())
+                .await?;
+        }
+
+        // All the tasks are sent, so workers could stop once they are done.
+        drop(self.tx);
+
+        self.tracker.wait().await;
+
+        apply_pair_fees(&self.database, &self.pair_fees).await?;
+
+        Ok(())
+    }
+
+    /// Sends the pairs of the factory which aren't indexed yet to the workers.
+    #[instrument(skip_all, fields(factory = factory.name))]
+    async fn index_factory(
+        &self,
+        factory: &Factory,
+        block_id: i32,
+        best_block_number: u64,
+    ) -> eyre::Result<()> {
+        let factory_contract = UniswapV2Factory::new(factory.address, self.eth_client.clone());
+
         let factory_id = self
             .database
-            .insert_factory(self.factory_contract.address())
+            .insert_factory(
+                factory.address,
+                &factory.name,
+                factory.fee.numerator,
+                factory.fee.denominator,
+            )
             .await?;
 
-        let last_indexed_pair = self.database.last_indexed_pair(factory_id).await? as u64;
+        let last_indexed_pair = self.database.last_indexed_pair(factory_id).await?;
+        let mut next_pair = last_indexed_pair.map_or(0, |number| number as u64 + 1);
 
-        let pairs_length = self
-            .factory_contract
-            .all_pairs_length()
-            .call()
-            .await?
-            .as_u64();
+        let pairs_length = factory_contract.all_pairs_length().call().await?.as_u64();
 
         tracing::info!(
-            "Last indexed pair: {}, pairs length: {}",
+            "Last indexed pair: {:?}, pairs length: {}",
             last_indexed_pair,
             pairs_length
         );
 
-        for pair_num in (last_indexed_pair + 1)..pairs_length {
+        if let Discovery::Logs {
+            from_block,
+            blocks_per_request,
+        } = self.discovery
+        {
+            let from_block = self
+                .database
+                .last_pair_creation_block(factory_id)
+                .await?
+                .map_or(from_block, |block| from_block.max(block as u64));
+
+            let result = self
+                .discover_from_logs(
+                    &factory_contract,
+                    factory_id,
+                    block_id,
+                    &mut next_pair,
+                    from_block,
+                    best_block_number,
+                    blocks_per_request,
+                )
+                .await;
+
+            if let Err(err) = result {
+                tracing::warn!(
+                    ?err,
+                    "Failed to discover pairs from logs, falling back to index enumeration"
+                );
+            }
+        }
+
+        // Picks up the pairs created after the scanned blocks, or all of
+        // them if logs aren't used.
+        for pair_num in next_pair..pairs_length {
             select! {
                 _ = self.cancellation.cancelled() => break,
-                _ = self.tx.send_async(Task {
+                _ = self.tx.send_async(Task::Pair {
                     factory_id,
+                    factory_address: factory.address,
                     block_id,
                     pair_num,
                 }) => {}
             }
         }
 
-        self.tracker.wait().await;
+        Ok(())
+    }
+
+    /// Scans `PairCreated` events of the factory and sends the pairs with
+    /// number starting from `next_pair` to the workers. Blocks are requested
+    /// by ranges of at most `blocks_per_request`, see [`LogsRange`].
+    #[allow(clippy::too_many_arguments)]
+    async fn discover_from_logs(
+        &self,
+        factory_contract: &UniswapV2Factory<Provider<Http>>,
+        factory_id: i32,
+        block_id: i32,
+        next_pair: &mut u64,
+        mut from_block: u64,
+        to_block: u64,
+        blocks_per_request: u64,
+    ) -> eyre::Result<()> {
+        let mut range = LogsRange::new(blocks_per_request);
+
+        while from_block <= to_block {
+            let range_end = range.end(from_block, to_block);
+
+            let result = factory_contract
+                .pair_created_filter()
+                .from_block(from_block)
+                .to_block(range_end)
+                .query_with_meta()
+                .await;
+            let Some(events) = range.check(result)? else {
+                continue;
+            };
+
+            for (event, meta) in events {
+                // The last argument of the event is the number of pairs after
+                // the creation.
+                let pair_num = event.p3.as_u64().saturating_sub(1);
+                if pair_num < *next_pair {
+                    continue;
+                }
+
+                let task = Task::CreatedPair {
+                    factory_id,
+                    block_id,
+                    pair: CreatedPair {
+                        address: event.pair,
+                        token0: event.token_0,
+                        token1: event.token_1,
+                        number: pair_num,
+                        created_at_block: meta.block_number.as_u64(),
+                    },
+                };
+
+                select! {
+                    _ = self.cancellation.cancelled() => return Ok(()),
+                    _ = self.tx.send_async(task) => {}
+                }
+
+                *next_pair = pair_num + 1;
+            }
+
+            tracing::info!(
+                "Scanned pairs created in blocks {}..={}",
+                from_block,
+                range_end
+            );
+
+            from_block = range_end + 1;
+        }
 
         Ok(())
     }
 }
 
+/// Stores the known fees of the pairs, the ones which aren't indexed yet are
+/// skipped.
+async fn apply_pair_fees(database: &DB, pair_fees: &[PairFee]) -> eyre::Result<()> {
+    let mut txn = database.pool().begin().await?;
+
+    for PairFee { pair, fee } in pair_fees {
+        let updated = DB::set_pair_fee(&mut txn, *pair, fee.numerator, fee.denominator).await?;
+
+        if !updated {
+            tracing::warn!("Pair {:?} with known fee isn't indexed yet", pair);
+        }
+    }
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+/// Walks back from the latest stored block and compares the stored hashes
+/// with the canonical chain. Blocks which are no longer canonical are removed
+/// together with the reserves fetched on them.
+///
+/// Return the pairs which reserves were removed and should be fetched again.
+pub(crate) async fn rollback_orphaned_blocks(
+    database: &DB,
+    eth_client: &Provider<Http>,
+) -> eyre::Result<Vec<PairEntry>> {
+    let stored_blocks = database.latest_blocks(MAX_REORG_DEPTH).await?;
+
+    let mut orphaned_blocks = Vec::new();
+    for block in stored_blocks {
+        let canonical_block = eth_client.get_block(block.height as u64).await?;
+
+        match canonical_block.and_then(|canonical| canonical.hash) {
+            Some(hash) if block.is_hash_of(hash) => break,
+            _ => orphaned_blocks.push(block),
+        }
+    }
+
+    if orphaned_blocks.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    tracing::warn!(
+        "Chain reorganization detected, rolling back {} blocks starting from height {}",
+        orphaned_blocks.len(),
+        orphaned_blocks[orphaned_blocks.len() - 1].height,
+    );
+
+    let block_ids: Vec<i32> = orphaned_blocks.iter().map(|block| block.id).collect();
+
+    let mut txn = database.pool().begin().await?;
+    let pairs = DB::delete_blocks(&mut txn, &block_ids).await?;
+    txn.commit().await?;
+
+    tracing::info!("Reserves of {} pairs will be fetched again", pairs.len());
+
+    Ok(pairs)
+}
+
+/// Size of the blocks ranges logs are requested for. It's halved each time
+/// the node refuses to return logs, e.g. as there are too many of them, and
+/// doubled back after successful requests up to the maximum one.
+pub(crate) struct LogsRange {
+    max: u64,
+    current: u64,
+}
+
+impl LogsRange {
+    pub(crate) fn new(max: u64) -> Self {
+        let max = max.max(1);
+
+        Self { max, current: max }
+    }
+
+    /// Return the last block of the range starting at `from_block`, not
+    /// going past `to_block`.
+    pub(crate) fn end(&self, from_block: u64, to_block: u64) -> u64 {
+        from_block.saturating_add(self.current - 1).min(to_block)
+    }
+
+    /// Adjust the range to the result of the request. Return `None` if the
+    /// request should be retried with the smaller range, and the error only
+    /// if logs can't be requested even for a single block.
+    pub(crate) fn check<T, E: Into<eyre::Report>>(
+        &mut self,
+        result: Result<T, E>,
+    ) -> eyre::Result<Option<T>> {
+        match result {
+            Ok(value) => {
+                self.current = self.current.saturating_mul(2).min(self.max);
+                Ok(Some(value))
+            }
+            Err(err) if self.current > 1 => {
+                self.current /= 2;
+                tracing::debug!(
+                    err = ?err.into(),
+                    blocks_per_request = self.current,
+                    "Failed to get logs, reducing blocks range"
+                );
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Number of the latest stored blocks checked for being orphaned by a chain
+/// reorganization.
+pub(crate) const MAX_REORG_DEPTH: i64 = 64;
+
+/// Return the addresses of the stored factories by their ids.
+///
+/// Factories stored in the shortened form are matched against the known
+/// addresses, and are left out if none of them matches.
+pub(crate) fn factory_addresses(
+    entries: &[FactoryEntry],
+    known: &[Address],
+) -> HashMap<i32, Address> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let address = match entry.address.trim_end().parse::<Address>() {
+                Ok(address) => address,
+                Err(_) => *known
+                    .iter()
+                    .find(|address| database::matches_address(&entry.address, **address))?,
+            };
+
+            Some((entry.id, address))
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Copy)]
-pub struct Task {
-    pub factory_id: i32,
-    pub block_id: i32,
-    pub pair_num: u64,
+pub enum Task {
+    /// Index a new pair by its number in the factory.
+    Pair {
+        factory_id: i32,
+        factory_address: Address,
+        block_id: i32,
+        pair_num: u64,
+    },
+    /// Index a new pair discovered from the `PairCreated` event.
+    CreatedPair {
+        factory_id: i32,
+        block_id: i32,
+        pair: CreatedPair,
+    },
+    /// Fetch again reserves of the already indexed pair.
+    Reserves {
+        pair_id: i32,
+        factory_address: Address,
+        block_id: i32,
+        pair_num: u64,
+    },
+}
+
+/// Pair as it's known from the `PairCreated` event.
+#[derive(Debug, Clone, Copy)]
+pub struct CreatedPair {
+    pub address: Address,
+    pub token0: Address,
+    pub token1: Address,
+
+    /// Number of the pair in the factory.
+    pub number: u64,
+
+    /// Height of the block the pair was created in.
+    pub created_at_block: u64,
 }
 
 /// Worker is responsible for processing a pair and inserting the data into the
@@ -143,7 +519,7 @@ pub struct Task {
 pub(crate) struct Worker {
     db: DB,
     eth_client: Arc<Provider<Http>>,
-    factory_contract: UniswapV2Factory<Provider<Http>>,
+    batcher: CallBatcher,
     cancellation: CancellationToken,
     rx: flume::Receiver<Task>,
 }
@@ -152,14 +528,14 @@ impl Worker {
     pub(crate) fn new(
         db: DB,
         eth_client: Arc<Provider<Http>>,
-        factory_contract: UniswapV2Factory<Provider<Http>>,
+        batcher: CallBatcher,
         cancellation: CancellationToken,
         rx: flume::Receiver<Task>,
     ) -> Self {
         Self {
             db,
             eth_client,
-            factory_contract,
+            batcher,
             cancellation,
             rx,
         }
@@ -172,12 +548,42 @@ impl Worker {
 
         loop {
             let task = select! {
-                msg = self.rx.recv_async() => msg? ,
+                // All the senders are dropped, so there is no more work.
+                msg = self.rx.recv_async() => match msg {
+                    Ok(task) => task,
+                    Err(_) => break,
+                },
                 _ = self.cancellation.cancelled() => break,
             };
 
             for _ in 0..RETRIES {
-                if let Err(err) = self.process_pair(task).await {
+                let result = match task {
+                    Task::Pair {
+                        factory_id,
+                        factory_address,
+                        block_id,
+                        pair_num,
+                    } => {
+                        self.process_pair(factory_id, factory_address, block_id, pair_num)
+                            .await
+                    }
+                    Task::CreatedPair {
+                        factory_id,
+                        block_id,
+                        pair,
+                    } => self.process_created_pair(factory_id, block_id, pair).await,
+                    Task::Reserves {
+                        pair_id,
+                        factory_address,
+                        block_id,
+                        pair_num,
+                    } => {
+                        self.process_reserves(pair_id, factory_address, block_id, pair_num)
+                            .await
+                    }
+                };
+
+                if let Err(err) = result {
                     tracing::warn!("Failed to process pair: {:?}", err);
                     tokio::time::sleep(RETRY_DELAY).await;
                 } else {
@@ -192,21 +598,58 @@ impl Worker {
     #[instrument(skip(self))]
     async fn process_pair(
         &self,
-        Task {
-            factory_id,
-            block_id,
-            pair_num,
-        }: Task,
+        factory_id: i32,
+        factory_address: Address,
+        block_id: i32,
+        pair_num: u64,
     ) -> eyre::Result<()> {
+        let factory_contract = UniswapV2Factory::new(factory_address, self.eth_client.clone());
+
         let pair_address = self
-            .factory_contract
-            .all_pairs(pair_num.into())
-            .call()
+            .batcher
+            .call(factory_contract.all_pairs(pair_num.into()))
             .await?;
 
-        let mut txn = self.db.pool().begin().await?;
+        let info = fetch_pair_info(&self.batcher, self.eth_client.clone(), pair_address).await?;
 
-        let info = fetch_pair_info(self.eth_client.clone(), pair_address).await?;
+        self.insert_pair_info(factory_id, block_id, pair_num, pair_address, info, None)
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn process_created_pair(
+        &self,
+        factory_id: i32,
+        block_id: i32,
+        pair: CreatedPair,
+    ) -> eyre::Result<()> {
+        let pair_contract = UniswapV2Pair::new(pair.address, self.eth_client.clone());
+        let (reserve0, reserve1, _) = self.batcher.call(pair_contract.get_reserves()).await?;
+
+        let info = PairInfo::new(pair.token0, pair.token1, reserve0, reserve1);
+
+        self.insert_pair_info(
+            factory_id,
+            block_id,
+            pair.number,
+            pair.address,
+            info,
+            Some(pair.created_at_block as i64),
+        )
+        .await
+    }
+
+    /// Inserts the pair with its tokens and reserves into the database.
+    async fn insert_pair_info(
+        &self,
+        factory_id: i32,
+        block_id: i32,
+        pair_num: u64,
+        pair_address: Address,
+        info: PairInfo,
+        created_at_block: Option<i64>,
+    ) -> eyre::Result<()> {
+        let mut txn = self.db.pool().begin().await?;
 
         let (token0_id, token1_id) = self
             .fetch_insert_tokens(&mut txn, info.token0, info.token1)
@@ -219,6 +662,7 @@ impl Worker {
             token0_id,
             token1_id,
             factory_id,
+            created_at_block,
         )
         .await?;
 
@@ -230,6 +674,32 @@ impl Worker {
         Ok(())
     }
 
+    #[instrument(skip(self))]
+    async fn process_reserves(
+        &self,
+        pair_id: i32,
+        factory_address: Address,
+        block_id: i32,
+        pair_num: u64,
+    ) -> eyre::Result<()> {
+        let factory_contract = UniswapV2Factory::new(factory_address, self.eth_client.clone());
+        let pair_address = self
+            .batcher
+            .call(factory_contract.all_pairs(pair_num.into()))
+            .await?;
+
+        let pair_contract = UniswapV2Pair::new(pair_address, self.eth_client.clone());
+        let (reserve0, reserve1, _) = self.batcher.call(pair_contract.get_reserves()).await?;
+
+        let mut txn = self.db.pool().begin().await?;
+        DB::insert_reserves(&mut txn, pair_id, reserve0, reserve1, block_id).await?;
+        txn.commit().await?;
+
+        tracing::info!("Updated reserves");
+
+        Ok(())
+    }
+
     /// Checks if the tokens exist in the database, if not, fetches the token info
     /// and inserts them into the database.
     ///
@@ -248,35 +718,63 @@ impl Worker {
             // Do nothing if both tokens exist already in the database
             (Some(token0_id), Some(token1_id)) => (token0_id, token1_id),
             (None, Some(token1_id)) => {
-                let info = fetch_erc20_info(self.eth_client.clone(), token0).await?;
+                let info = fetch_erc20_info(&self.batcher, self.eth_client.clone(), token0).await?;
 
-                let token0_id =
-                    DB::insert_token(txn, token0, info.name, info.symbol, info.decimals).await?;
+                let token0_id = DB::insert_token(
+                    txn,
+                    token0,
+                    info.name,
+                    info.symbol,
+                    info.decimals,
+                    info.metadata_status,
+                )
+                .await?;
 
                 (token0_id, token1_id)
             }
             (Some(token0_id), None) => {
-                let info = fetch_erc20_info(self.eth_client.clone(), token1).await?;
+                let info = fetch_erc20_info(&self.batcher, self.eth_client.clone(), token1).await?;
 
-                let token1_id =
-                    DB::insert_token(txn, token1, info.name, info.symbol, info.decimals).await?;
+                let token1_id = DB::insert_token(
+                    txn,
+                    token1,
+                    info.name,
+                    info.symbol,
+                    info.decimals,
+                    info.metadata_status,
+                )
+                .await?;
 
                 (token0_id, token1_id)
             }
             // If none of the tokens exist, fetch both concurrently and insert them
             (None, None) => {
                 let responses = future::join(
-                    fetch_erc20_info(self.eth_client.clone(), token0),
-                    fetch_erc20_info(self.eth_client.clone(), token1),
+                    fetch_erc20_info(&self.batcher, self.eth_client.clone(), token0),
+                    fetch_erc20_info(&self.batcher, self.eth_client.clone(), token1),
                 )
                 .await;
                 let info0 = responses.0?;
                 let info1 = responses.1?;
 
-                let token0_id =
-                    DB::insert_token(txn, token0, info0.name, info0.symbol, info0.decimals).await?;
-                let token1_id =
-                    DB::insert_token(txn, token1, info1.name, info1.symbol, info1.decimals).await?;
+                let token0_id = DB::insert_token(
+                    txn,
+                    token0,
+                    info0.name,
+                    info0.symbol,
+                    info0.decimals,
+                    info0.metadata_status,
+                )
+                .await?;
+                let token1_id = DB::insert_token(
+                    txn,
+                    token1,
+                    info1.name,
+                    info1.symbol,
+                    info1.decimals,
+                    info1.metadata_status,
+                )
+                .await?;
 
                 (token0_id, token1_id)
             }
@@ -295,40 +793,65 @@ pub struct TokenInfo {
 
     /// The number of decimals of the token.
     pub decimals: u8,
+
+    /// Which of the fields were decoded from the token.
+    pub metadata_status: MetadataStatus,
 }
 
 impl TokenInfo {
-    pub fn new(name: String, symbol: String, decimals: u8) -> Self {
+    /// Fill the fields which couldn't be fetched with placeholders, 18
+    /// decimals are assumed as the most common ones.
+    pub fn new(name: Option<String>, symbol: Option<String>, decimals: Option<u8>) -> Self {
+        let metadata_status = match (&name, &symbol, decimals) {
+            (_, _, None) => MetadataStatus::Guessed,
+            (Some(_), Some(_), Some(_)) => MetadataStatus::Complete,
+            _ => MetadataStatus::Partial,
+        };
+
         Self {
-            name,
-            symbol,
-            decimals,
+            name: name.unwrap_or_else(|| "unknown".to_string()),
+            symbol: symbol.unwrap_or_else(|| "unknown".to_string()),
+            decimals: decimals.unwrap_or(18),
+            metadata_status,
         }
     }
 }
 
-/// Performs a multicall to fetch the ERC20 token info which includes the
-/// name, symbol and decimals
-#[instrument(skip(client))]
+/// Fetches the ERC20 token info which includes the name, symbol and
+/// decimals as part of the batched calls.
+///
+/// Each field is decoded separately, accepting `bytes32` names and symbols,
+/// and falls back to a placeholder if the token doesn't implement it. The
+/// error is returned only if the batch itself failed.
+#[instrument(skip(batcher, client))]
 pub async fn fetch_erc20_info(
+    batcher: &CallBatcher,
     client: Arc<Provider<Http>>,
     address: H160,
 ) -> Result<TokenInfo, eyre::Error> {
-    let token_contract = IUniswapV2ERC20::new(address, client.clone());
+    let token_contract = IUniswapV2ERC20::new(address, client);
+
+    let (name, symbol, decimals) = future::try_join3(
+        batcher.try_call_raw(&token_contract.name()),
+        batcher.try_call_raw(&token_contract.symbol()),
+        batcher.try_call_raw(&token_contract.decimals()),
+    )
+    .await?;
 
-    let mut multicall = Multicall::new(client, None).await?;
-    multicall
-        .add_call(token_contract.name(), false)
-        .add_call(token_contract.symbol(), false)
-        .add_call(token_contract.decimals(), false);
+    let info = TokenInfo::new(
+        name.as_deref().and_then(metadata::decode_text),
+        symbol.as_deref().and_then(metadata::decode_text),
+        decimals.as_deref().and_then(metadata::decode_decimals),
+    );
 
-    let (name, symbol, decimals): (String, String, u8) =
-        multicall.call().await.unwrap_or_else(|err| {
-            tracing::warn!("Failed to fetch token info: {:?}", err);
-            ("unknown".to_string(), "unknown".to_string(), 18)
-        });
+    if info.metadata_status != MetadataStatus::Complete {
+        tracing::warn!(
+            status = info.metadata_status.as_str(),
+            "Failed to fetch complete token info"
+        );
+    }
 
-    Ok(TokenInfo::new(name, symbol, decimals))
+    Ok(info)
 }
 
 #[derive(Debug)]
@@ -350,23 +873,210 @@ impl PairInfo {
     }
 }
 
-/// Fetches the pair info from the chain using a multicall.
+/// Fetches the pair info from the chain as part of the batched calls.
 ///
 /// The pair info includes the token0, token1, reserve0 and reserve1.
 pub async fn fetch_pair_info(
+    batcher: &CallBatcher,
     client: Arc<Provider<Http>>,
     address: Address,
 ) -> Result<PairInfo, eyre::Error> {
-    let pair_contract = UniswapV2Pair::new(address, client.clone());
+    let pair_contract = UniswapV2Pair::new(address, client);
 
-    let mut multicall = Multicall::new(client, None).await?;
-    multicall
-        .add_call(pair_contract.token_0(), false)
-        .add_call(pair_contract.token_1(), false)
-        .add_call(pair_contract.get_reserves(), false);
-
-    let (token0, token1, (reserve0, reserve1, _)): (H160, H160, (u128, u128, u32)) =
-        multicall.call().await?;
+    let (token0, token1, (reserve0, reserve1, _)) = future::try_join3(
+        batcher.call(pair_contract.token_0()),
+        batcher.call(pair_contract.token_1()),
+        batcher.call(pair_contract.get_reserves()),
+    )
+    .await?;
 
     Ok(PairInfo::new(token0, token1, reserve0, reserve1))
 }
+
+#[cfg(test)]
+mod tests {
+    use database::{
+        prices::NewTokenPrice,
+        trades::{LiquidityEventKind, NewLiquidityEvent, NewSwap},
+    };
+    use ethers::types::{Block, H256, U256};
+    use sqlx::types::BigDecimal;
+
+    use crate::testing;
+
+    use super::*;
+
+    #[test]
+    fn test_logs_range() -> eyre::Result<()> {
+        let failed = || Err::<(), _>(eyre::eyre!("Too many logs"));
+
+        let mut range = LogsRange::new(8);
+        assert_eq!(range.end(10, 100), 17);
+        assert_eq!(range.end(10, 12), 12);
+
+        assert_eq!(range.check(failed())?, None);
+        assert_eq!(range.check(failed())?, None);
+        assert_eq!(range.end(10, 100), 11);
+
+        // The range grows back after successful requests, up to the maximum.
+        assert_eq!(range.check(Ok::<_, eyre::Report>(1))?, Some(1));
+        assert_eq!(range.end(10, 100), 13);
+        range.check(Ok::<_, eyre::Report>(()))?;
+        range.check(Ok::<_, eyre::Report>(()))?;
+        assert_eq!(range.end(10, 100), 17);
+
+        let mut range = LogsRange::new(1);
+        assert!(range.check(failed()).is_err());
+
+        Ok(())
+    }
+
+    /// Mine an empty block and return it.
+    async fn mine_block(provider: &Provider<Http>) -> eyre::Result<Block<H256>> {
+        provider
+            .request::<_, serde_json::Value>("evm_mine", ())
+            .await?;
+
+        let number = provider.get_block_number().await?;
+        provider
+            .get_block(number)
+            .await?
+            .ok_or_else(|| eyre::eyre!("Block {} not found", number))
+    }
+
+    /// Store reserves, a price, a swap and a liquidity event of the pair on
+    /// the block.
+    async fn store_block_data(database: &DB, pair: &PairEntry, block_id: i32) -> eyre::Result<()> {
+        let mut txn = database.pool().begin().await?;
+
+        DB::insert_reserves(&mut txn, pair.id, 1_000, 2_000, block_id).await?;
+        DB::insert_token_prices(
+            &mut txn,
+            block_id,
+            pair.token1,
+            &[NewTokenPrice {
+                token: pair.token0,
+                price_base: BigDecimal::from(2),
+                price_usd: None,
+                liquidity: Some(BigDecimal::from(1)),
+            }],
+        )
+        .await?;
+        DB::insert_swaps(
+            &mut txn,
+            block_id,
+            &[NewSwap {
+                pair: pair.id,
+                tx_hash: H256::random(),
+                log_index: 0,
+                sender: Address::random(),
+                recipient: Address::random(),
+                amount0_in: U256::from(10),
+                amount1_in: U256::zero(),
+                amount0_out: U256::zero(),
+                amount1_out: U256::from(19),
+            }],
+        )
+        .await?;
+        DB::insert_liquidity_events(
+            &mut txn,
+            block_id,
+            &[NewLiquidityEvent {
+                pair: pair.id,
+                tx_hash: H256::random(),
+                log_index: 1,
+                kind: LiquidityEventKind::Mint,
+                sender: Address::random(),
+                recipient: None,
+                amount0: U256::from(10),
+                amount1: U256::from(20),
+            }],
+        )
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Reverts the chain to a snapshot and mines other blocks at the heights
+    /// of the stored ones, which must be rolled back down to the last block
+    /// mined before the snapshot.
+    ///
+    /// Requires a local Anvil node at `ETH_RPC_URL` and the database at
+    /// `DATABASE_URL` with at least one indexed pair and no blocks of other
+    /// chains:
+    ///
+    /// ```sh
+    /// anvil & cargo test -p bootstrapper -- --ignored
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires a local Anvil node"]
+    async fn test_rollback_orphaned_blocks() -> eyre::Result<()> {
+        let provider = testing::anvil_provider()?;
+        let database = DB::new().await?;
+
+        let pair = database
+            .pairs_after(0)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre::eyre!("No pairs are indexed"))?;
+
+        let canonical_id = database.insert_block(mine_block(&provider).await?).await?;
+        store_block_data(&database, &pair, canonical_id).await?;
+
+        let snapshot: U256 = provider.request("evm_snapshot", ()).await?;
+
+        let mut orphaned_ids = Vec::new();
+        for _ in 0..3 {
+            let block_id = database.insert_block(mine_block(&provider).await?).await?;
+            store_block_data(&database, &pair, block_id).await?;
+            orphaned_ids.push(block_id);
+        }
+
+        let reverted: bool = provider.request("evm_revert", [snapshot]).await?;
+        assert!(reverted);
+
+        // Empty blocks mined at the same time would get the same hashes.
+        provider
+            .request::<_, serde_json::Value>("evm_increaseTime", [60])
+            .await?;
+        for _ in 0..3 {
+            mine_block(&provider).await?;
+        }
+
+        let pairs = rollback_orphaned_blocks(&database, &provider).await?;
+        assert_eq!(
+            pairs.iter().map(|pair| pair.id).collect::<Vec<_>>(),
+            vec![pair.id]
+        );
+
+        let stored_ids: Vec<i32> = sqlx::query_scalar("SELECT id FROM blocks WHERE id = ANY($1)")
+            .bind([&orphaned_ids[..], &[canonical_id]].concat())
+            .fetch_all(database.pool())
+            .await?;
+        assert_eq!(stored_ids, vec![canonical_id]);
+
+        for table in ["reserves", "token_prices", "swaps", "liquidity_events"] {
+            let blocks: Vec<i32> = sqlx::query_scalar(&format!(
+                "SELECT block FROM {} WHERE block = ANY($1)",
+                table
+            ))
+            .bind([&orphaned_ids[..], &[canonical_id]].concat())
+            .fetch_all(database.pool())
+            .await?;
+            assert_eq!(
+                blocks,
+                vec![canonical_id],
+                "{} of the orphaned blocks",
+                table
+            );
+        }
+
+        let mut conn = database.pool().acquire().await?;
+        DB::delete_blocks(&mut conn, &[canonical_id]).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use bindings::uniswap_v2_pair::{
+    BurnFilter, MintFilter, SwapFilter, SyncFilter, UniswapV2PairEvents,
+};
+use color_eyre::eyre;
+use database::{
+    trades::{LiquidityEventKind, NewLiquidityEvent, NewSwap},
+    DB,
+};
+use ethers::{
+    abi::Address,
+    contract::{parse_log, EthEvent},
+    providers::{Http, Middleware, Provider},
+    types::{Filter, Log, H256},
+};
+use sqlx::PgConnection;
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
+
+use crate::fetcher::{rollback_orphaned_blocks, LogsRange, MAX_REORG_DEPTH};
+
+/// Maximum number of pair addresses in a single logs request.
+const ADDRESSES_PER_REQUEST: usize = 1_000;
+
+/// Follower keeps reserves of the indexed pairs up to date by reading `Sync`
+/// events of the pairs block by block, and stores their swaps, mints and
+/// burns.
+pub struct Follower {
+    /// Database connector to store new reserves
+    database: DB,
+
+    /// Client to interact with the Ethereum network
+    eth_client: Arc<Provider<Http>>,
+
+    /// Cancellation token to stop the follower
+    cancellation: CancellationToken,
+
+    /// Known pairs by their address with ids in the database
+    pairs: HashMap<Address, i32>,
+
+    /// Id of the last pair loaded from the database
+    last_pair_id: i32,
+
+    /// Number and hash of the last processed block
+    head: Option<(u64, H256)>,
+
+    config: FollowerConfig,
+}
+
+pub struct FollowerConfig {
+    pub db_url: String,
+    pub eth_url: String,
+
+    /// Block to start following from, if not set the block after the last
+    /// processed one is used.
+    pub from_block: Option<u64>,
+
+    /// Delay between polls of the new blocks.
+    pub poll_interval: Duration,
+
+    /// Maximum number of blocks to request logs for at once.
+    pub blocks_per_request: u64,
+}
+
+impl Follower {
+    pub async fn try_from_config(
+        config: FollowerConfig,
+        cancellation: CancellationToken,
+    ) -> eyre::Result<Self> {
+        let database = DB::from_url(&config.db_url).await?;
+        let eth_client = Arc::new(Provider::<Http>::try_from(config.eth_url.as_str())?);
+
+        Ok(Self::new(database, eth_client, config, cancellation))
+    }
+
+    pub fn new(
+        database: DB,
+        eth_client: Arc<Provider<Http>>,
+        config: FollowerConfig,
+        cancellation: CancellationToken,
+    ) -> Self {
+        Self {
+            database,
+            eth_client,
+            cancellation,
+            pairs: HashMap::new(),
+            last_pair_id: 0,
+            head: None,
+            config,
+        }
+    }
+
+    pub async fn run(mut self) -> eyre::Result<()> {
+        let mut next_block = match self.config.from_block {
+            Some(from_block) => from_block,
+            None => {
+                let mut conn = self.database.pool().acquire().await?;
+
+                match resume_block(&mut conn).await? {
+                    Some(block) => block,
+                    None => self.eth_client.get_block_number().await?.as_u64(),
+                }
+            }
+        };
+
+        tracing::info!("Following reserves from block {}", next_block);
+
+        let mut range = LogsRange::new(self.config.blocks_per_request);
+
+        loop {
+            self.load_new_pairs().await?;
+
+            if self.is_head_orphaned().await? {
+                rollback_orphaned_blocks(&self.database, &self.eth_client).await?;
+
+                // Blocks without `Sync` events aren't stored, so the exact
+                // fork point is unknown, the whole reorg window is processed
+                // again instead.
+                let (head_number, _) = self.head.take().expect("head is checked above");
+                next_block = next_block.min(head_number.saturating_sub(MAX_REORG_DEPTH as u64) + 1);
+
+                let mut conn = self.database.pool().acquire().await?;
+                DB::set_follower_height(&mut conn, next_block as i64 - 1).await?;
+            }
+
+            let best_block = self.eth_client.get_block_number().await?.as_u64();
+
+            while next_block <= best_block && !self.cancellation.is_cancelled() {
+                let to_block = range.end(next_block, best_block);
+
+                let result = self.fetch_logs(next_block, to_block).await;
+                let Some(logs) = range.check(result)? else {
+                    continue;
+                };
+
+                self.process_blocks(to_block, logs).await?;
+
+                next_block = to_block + 1;
+            }
+
+            select! {
+                _ = self.cancellation.cancelled() => break,
+                _ = tokio::time::sleep(self.config.poll_interval) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load pairs indexed since the last call.
+    async fn load_new_pairs(&mut self) -> eyre::Result<()> {
+        let new_pairs = self.database.pairs_after(self.last_pair_id).await?;
+
+        let mut skipped = 0;
+        for pair in new_pairs {
+            self.last_pair_id = pair.id;
+
+            match pair.address.trim_end().parse::<Address>() {
+                Ok(address) => {
+                    self.pairs.insert(address, pair.id);
+                }
+                Err(_) => skipped += 1,
+            }
+        }
+
+        if skipped > 0 {
+            tracing::warn!(
+                "Skipped {} pairs with shortened addresses, run `migrate-addresses` to follow them",
+                skipped
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Check if the last processed block is still in the canonical chain.
+    async fn is_head_orphaned(&self) -> eyre::Result<bool> {
+        let Some((number, hash)) = self.head else {
+            return Ok(false);
+        };
+
+        let canonical_block = self.eth_client.get_block(number).await?;
+
+        Ok(canonical_block.and_then(|block| block.hash) != Some(hash))
+    }
+
+    /// Request events of the followed pairs emitted in the blocks range.
+    ///
+    /// Nodes limit the number of addresses per filter, so the pairs are
+    /// requested by chunks, and the logs of all the chunks are ordered by
+    /// their position in the chain.
+    async fn fetch_logs(&self, from_block: u64, to_block: u64) -> eyre::Result<Vec<Log>> {
+        let addresses: Vec<Address> = self.pairs.keys().copied().collect();
+
+        let mut logs = Vec::new();
+        for filter in logs_filters(&addresses, from_block, to_block) {
+            logs.extend(self.eth_client.get_logs(&filter).await?);
+        }
+
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+        Ok(logs)
+    }
+
+    /// Store the latest reserves of each pair changed by the logs for each
+    /// block, together with the swaps and liquidity changes. `to_block` is
+    /// the end of the range the logs were requested for.
+    #[instrument(skip(self, logs), fields(logs = logs.len()))]
+    async fn process_blocks(&mut self, to_block: u64, logs: Vec<Log>) -> eyre::Result<()> {
+        let mut changes: BTreeMap<u64, BlockChanges> = BTreeMap::new();
+        for log in logs {
+            if log.removed == Some(true) {
+                continue;
+            }
+
+            let Some(pair_id) = self.pairs.get(&log.address).copied() else {
+                continue;
+            };
+
+            let block_number = log
+                .block_number
+                .ok_or_else(|| eyre::eyre!("Log block number is missing"))?
+                .as_u64();
+
+            let tx_hash = log
+                .transaction_hash
+                .ok_or_else(|| eyre::eyre!("Log transaction hash is missing"))?;
+            let log_index = log
+                .log_index
+                .ok_or_else(|| eyre::eyre!("Log index is missing"))?
+                .as_u64();
+
+            let block_changes = changes.entry(block_number).or_default();
+
+            match parse_log(log)? {
+                // Logs are ordered by block and index inside it, so only the
+                // last reserves of the pair in each block are kept.
+                UniswapV2PairEvents::SyncFilter(sync) => {
+                    block_changes
+                        .reserves
+                        .insert(pair_id, (sync.reserve_0, sync.reserve_1));
+                }
+                UniswapV2PairEvents::SwapFilter(swap) => block_changes.swaps.push(NewSwap {
+                    pair: pair_id,
+                    tx_hash,
+                    log_index,
+                    sender: swap.sender,
+                    recipient: swap.to,
+                    amount0_in: swap.amount_0_in,
+                    amount1_in: swap.amount_1_in,
+                    amount0_out: swap.amount_0_out,
+                    amount1_out: swap.amount_1_out,
+                }),
+                UniswapV2PairEvents::MintFilter(mint) => {
+                    block_changes.liquidity_events.push(NewLiquidityEvent {
+                        pair: pair_id,
+                        tx_hash,
+                        log_index,
+                        kind: LiquidityEventKind::Mint,
+                        sender: mint.sender,
+                        recipient: None,
+                        amount0: mint.amount_0,
+                        amount1: mint.amount_1,
+                    })
+                }
+                UniswapV2PairEvents::BurnFilter(burn) => {
+                    block_changes.liquidity_events.push(NewLiquidityEvent {
+                        pair: pair_id,
+                        tx_hash,
+                        log_index,
+                        kind: LiquidityEventKind::Burn,
+                        sender: burn.sender,
+                        recipient: Some(burn.to),
+                        amount0: burn.amount_0,
+                        amount1: burn.amount_1,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        for (
+            block_number,
+            BlockChanges {
+                reserves,
+                swaps,
+                liquidity_events,
+            },
+        ) in changes
+        {
+            let block = self
+                .eth_client
+                .get_block(block_number)
+                .await?
+                .ok_or_else(|| eyre::eyre!("Block {} not found", block_number))?;
+
+            let block_id = self.database.insert_block(block).await?;
+
+            let mut txn = self.database.pool().begin().await?;
+            for (pair_id, (reserve0, reserve1)) in &reserves {
+                DB::insert_reserves(&mut txn, *pair_id, *reserve0, *reserve1, block_id).await?;
+            }
+            DB::insert_swaps(&mut txn, block_id, &swaps).await?;
+            DB::insert_liquidity_events(&mut txn, block_id, &liquidity_events).await?;
+            txn.commit().await?;
+
+            tracing::info!(
+                swaps = swaps.len(),
+                liquidity_events = liquidity_events.len(),
+                "Updated reserves of {} pairs at block {}",
+                reserves.len(),
+                block_number,
+            );
+        }
+
+        let head = self
+            .eth_client
+            .get_block(to_block)
+            .await?
+            .and_then(|block| block.hash)
+            .ok_or_else(|| eyre::eyre!("Block {} not found", to_block))?;
+
+        self.head = Some((to_block, head));
+
+        let mut conn = self.database.pool().acquire().await?;
+        DB::set_follower_height(&mut conn, to_block as i64).await?;
+
+        Ok(())
+    }
+}
+
+/// Return the block to resume following from, the one after the last block
+/// processed by the follower. On the first run it's the block after the
+/// latest stored one, i.e. the block `run` fetched the reserves at.
+async fn resume_block(conn: &mut PgConnection) -> eyre::Result<Option<u64>> {
+    let height = match DB::follower_height(&mut *conn).await? {
+        Some(height) => Some(height),
+        None => DB::latest_block_height(conn).await?,
+    };
+
+    Ok(height.map(|height| height as u64 + 1))
+}
+
+/// Return filters of the pair events in the blocks range, each for at most
+/// [`ADDRESSES_PER_REQUEST`] of the addresses. No filters are returned
+/// without addresses, as a filter without them matches the logs of every
+/// contract.
+fn logs_filters(addresses: &[Address], from_block: u64, to_block: u64) -> Vec<Filter> {
+    addresses
+        .chunks(ADDRESSES_PER_REQUEST)
+        .map(|chunk| {
+            Filter::new()
+                .address(chunk.to_vec())
+                .from_block(from_block)
+                .to_block(to_block)
+                .topic0(vec![
+                    SyncFilter::signature(),
+                    SwapFilter::signature(),
+                    MintFilter::signature(),
+                    BurnFilter::signature(),
+                ])
+        })
+        .collect()
+}
+
+/// Changes of the pairs in a single block.
+#[derive(Default)]
+struct BlockChanges {
+    /// The latest reserves of the changed pairs.
+    reserves: HashMap<i32, (u128, u128)>,
+
+    swaps: Vec<NewSwap>,
+    liquidity_events: Vec<NewLiquidityEvent>,
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::ValueOrArray;
+
+    use super::*;
+
+    #[test]
+    fn test_logs_filters() {
+        assert!(logs_filters(&[], 1, 10).is_empty());
+
+        let addresses: Vec<Address> = (0..ADDRESSES_PER_REQUEST as u64 * 2 + 1)
+            .map(Address::from_low_u64_be)
+            .collect();
+        let filters = logs_filters(&addresses, 1, 10);
+
+        let chunks: Vec<Vec<Address>> = filters
+            .iter()
+            .map(|filter| match &filter.address {
+                Some(ValueOrArray::Array(chunk)) => chunk.clone(),
+                other => panic!("Filter isn't limited to the pairs: {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![ADDRESSES_PER_REQUEST, ADDRESSES_PER_REQUEST, 1]
+        );
+        assert_eq!(chunks.concat(), addresses);
+    }
+
+    /// Store a block the way `run` does after fetching the reserves.
+    async fn store_block(conn: &mut PgConnection, height: i64) -> eyre::Result<()> {
+        sqlx::query("INSERT INTO blocks (height, hash) VALUES ($1, $2)")
+            .bind(height)
+            .bind(format!("{:?}", H256::from_low_u64_be(height as u64)))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Requires the database at `DATABASE_URL`, the changes are rolled back.
+    #[tokio::test]
+    #[ignore = "requires a local Postgres"]
+    async fn test_resume_block() -> eyre::Result<()> {
+        let database = DB::new().await?;
+        let mut txn = database.pool().begin().await?;
+
+        sqlx::query("DELETE FROM follower_cursor")
+            .execute(&mut *txn)
+            .await?;
+
+        let run_height = DB::latest_block_height(&mut txn).await?.unwrap_or(0) + 100;
+        store_block(&mut txn, run_height).await?;
+
+        // The first run follows from the block the reserves were fetched at.
+        assert_eq!(resume_block(&mut txn).await?, Some(run_height as u64 + 1));
+
+        DB::set_follower_height(&mut txn, run_height + 10).await?;
+        store_block(&mut txn, run_height + 100).await?;
+
+        // A newer block stored by `run` doesn't skip the blocks in between.
+        assert_eq!(resume_block(&mut txn).await?, Some(run_height as u64 + 11));
+
+        Ok(())
+    }
+}
@@ -3,8 +3,13 @@ use color_eyre::eyre;
 
 use crate::cli::Cli;
 
-mod fetcher;
+mod batcher;
 mod cli;
+mod fetcher;
+mod follower;
+mod metadata;
+#[cfg(test)]
+mod testing;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -0,0 +1,88 @@
+//! Decoding of the ERC20 metadata returned by the tokens which don't follow
+//! the standard, e.g. MKR returning `bytes32` name and symbol.
+
+use ethers::{
+    abi::{self, ParamType, Token},
+    types::U256,
+};
+
+/// Maximum number of characters of the stored name or symbol.
+const MAX_TEXT_LENGTH: usize = 128;
+
+/// Decode the name or the symbol returned either as `string` or `bytes32`,
+/// return `None` if nothing readable is left after sanitization.
+pub fn decode_text(data: &[u8]) -> Option<String> {
+    let bytes = if data.len() == 32 {
+        // Padded with zeros on the right.
+        let end = data
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(data.len());
+        data[..end].to_vec()
+    } else {
+        // Decoded as `bytes` to not fail on invalid UTF-8.
+        match abi::decode(&[ParamType::Bytes], data).ok()?.pop()? {
+            Token::Bytes(bytes) => bytes,
+            _ => return None,
+        }
+    };
+
+    sanitize(&String::from_utf8_lossy(&bytes))
+}
+
+/// Decode the decimals returned as any `uint`, return `None` if they don't
+/// fit into `uint8`.
+pub fn decode_decimals(data: &[u8]) -> Option<u8> {
+    if data.len() < 32 {
+        return None;
+    }
+
+    let decimals = U256::from_big_endian(&data[..32]);
+    (decimals <= U256::from(u8::MAX)).then(|| decimals.as_u32() as u8)
+}
+
+fn sanitize(text: &str) -> Option<String> {
+    let text: String = text
+        .chars()
+        .filter(|char| !char.is_control())
+        .take(MAX_TEXT_LENGTH)
+        .collect();
+    let text = text.trim();
+
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_text() {
+        let encoded = abi::encode(&[Token::String("Wrapped Ether".to_string())]);
+        assert_eq!(decode_text(&encoded).as_deref(), Some("Wrapped Ether"));
+
+        // Symbol of MKR.
+        let mut bytes32 = [0u8; 32];
+        bytes32[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_text(&bytes32).as_deref(), Some("MKR"));
+
+        let encoded = abi::encode(&[Token::Bytes(vec![b'A', 0xff, b'B', b'\n'])]);
+        assert_eq!(decode_text(&encoded).as_deref(), Some("A\u{fffd}B"));
+
+        assert_eq!(decode_text(&[0u8; 32]), None);
+        assert_eq!(decode_text(&[]), None);
+    }
+
+    #[test]
+    fn test_decode_decimals() {
+        assert_eq!(
+            decode_decimals(&abi::encode(&[Token::Uint(6.into())])),
+            Some(6)
+        );
+        assert_eq!(
+            decode_decimals(&abi::encode(&[Token::Uint(256.into())])),
+            None
+        );
+        assert_eq!(decode_decimals(&[]), None);
+    }
+}
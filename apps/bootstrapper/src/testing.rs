@@ -0,0 +1,17 @@
+//! Helpers shared by the tests.
+
+use color_eyre::eyre;
+use ethers::providers::{Http, Provider};
+
+/// Client of the local Anvil node at `ETH_RPC_URL`, `127.0.0.1:8545` by
+/// default, for the tests ignored by default.
+pub fn anvil_provider() -> eyre::Result<Provider<Http>> {
+    let url = std::env::var("ETH_RPC_URL").unwrap_or_else(|_| "127.0.0.1:8545".to_string());
+    let url = if url.contains("://") {
+        url
+    } else {
+        format!("http://{}", url)
+    };
+
+    Ok(Provider::<Http>::try_from(url.as_str())?)
+}
@@ -0,0 +1,364 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre;
+use config::Config;
+use database::DB;
+use ethers::types::U256;
+
+use super::find::{factory_names, get_token, print_route, PairEdge, PairsGraph, Route};
+use crate::{cli::ArbitrageArgs, filters::GraphFilter, graph::PairGraph, math};
+
+/// Minimal decrease of the distance to count as a relaxation, so rounding
+/// errors don't make zero weight cycles look profitable.
+const EPSILON: f64 = 1e-9;
+
+/// Directed swap through the pair, weighted by the negative logarithm of its
+/// marginal price including the fee, so that cycles with the product of the
+/// prices above one have negative weight.
+struct SwapEdge<'a> {
+    token_in: i32,
+    token_out: i32,
+    weight: f64,
+    pair: &'a PairEdge,
+}
+
+/// Profitable cycle sized with its optimal input amount.
+struct Opportunity {
+    route: Route,
+    profit: U256,
+}
+
+pub async fn arbitrage(config: Config, args: ArbitrageArgs) -> eyre::Result<()> {
+    let base_token = args
+        .base_token
+        .or(config.pathfinder.base_token)
+        .ok_or_else(|| {
+            eyre::eyre!("Base token is set neither in the arguments nor in the config")
+        })?;
+
+    let database = DB::from_url(&config.database.url).await?;
+
+    let mut txn = database.pool().begin().await?;
+
+    let filter = GraphFilter::load(&mut txn, &config.pathfinder.filters, Some(base_token)).await?;
+    let at_block = args.at_block.map(|block| block as i64);
+    let pair_graph = PairGraph::load(&mut txn, filter, at_block).await?;
+    let graph = pair_graph.graph();
+
+    let base_token = get_token(&mut txn, base_token).await?;
+
+    let mut opportunities: Vec<Opportunity> =
+        find_negative_cycles(graph, base_token.id, args.rounds)
+            .iter()
+            .filter_map(|cycle| size_cycle(cycle_route(cycle, base_token.id)))
+            .collect();
+
+    opportunities.sort_by(|a, b| b.profit.cmp(&a.profit));
+    opportunities.truncate(args.limit);
+
+    if opportunities.is_empty() {
+        println!("No profitable cycles found from {}", base_token.symbol);
+        return Ok(());
+    }
+
+    let factory_names = factory_names(&mut txn).await?;
+
+    for Opportunity { route, profit } in &opportunities {
+        // Cycles not going through the base token are traded in their first
+        // token.
+        let symbol = DB::token_by_id(&mut txn, route.tokens[0])
+            .await?
+            .map_or_else(|| route.tokens[0].to_string(), |token| token.symbol);
+
+        print_route(&mut txn, route, &factory_names).await?;
+        println!(
+            "  Optimal input: {} {}, output: {} {}, profit: {} {}",
+            route.amount_in, symbol, route.amount_out, symbol, profit, symbol,
+        );
+    }
+
+    Ok(())
+}
+
+/// Return swaps in both directions of every pair of the graph with non zero
+/// reserves.
+fn swap_edges(graph: &PairsGraph) -> Vec<SwapEdge<'_>> {
+    let mut edges = Vec::new();
+
+    for (token_a, token_b, pairs) in graph.all_edges() {
+        for pair in pairs {
+            for (token_in, token_out) in [(token_a, token_b), (token_b, token_a)] {
+                let (reserve_in, reserve_out) = pair.reserves_from(token_in);
+                if reserve_in.is_zero() || reserve_out.is_zero() {
+                    continue;
+                }
+
+                let price = math::spot_rate(reserve_in, reserve_out, pair.fee);
+
+                edges.push(SwapEdge {
+                    token_in,
+                    token_out,
+                    weight: -price.ln(),
+                    pair,
+                });
+            }
+        }
+    }
+
+    edges
+}
+
+/// Find cycles with the product of marginal prices above one, reachable from
+/// the `base` token, as lists of `(token_in, pair)` swaps.
+///
+/// Bellman-Ford relaxation is run from the `base` token for at most `rounds`
+/// rounds. After each round the predecessor graph is checked for cycles, as
+/// any cycle there has negative weight, so short cycles are found without
+/// running all the `V - 1` rounds.
+fn find_negative_cycles(
+    graph: &PairsGraph,
+    base: i32,
+    rounds: usize,
+) -> Vec<Vec<(i32, &PairEdge)>> {
+    let edges = swap_edges(graph);
+
+    let mut distances: HashMap<i32, f64> = HashMap::from([(base, 0.0)]);
+    let mut predecessors: HashMap<i32, usize> = HashMap::new();
+
+    let mut known_cycles: HashSet<Vec<usize>> = HashSet::new();
+    let mut cycles = Vec::new();
+
+    for _ in 0..rounds {
+        let mut is_relaxed = false;
+
+        for (i, edge) in edges.iter().enumerate() {
+            let Some(distance) = distances.get(&edge.token_in) else {
+                continue;
+            };
+
+            let candidate = distance + edge.weight;
+            let is_shorter = distances
+                .get(&edge.token_out)
+                .map_or(true, |known| candidate < known - EPSILON);
+
+            if is_shorter {
+                distances.insert(edge.token_out, candidate);
+                predecessors.insert(edge.token_out, i);
+                is_relaxed = true;
+            }
+        }
+
+        for cycle in predecessor_cycles(&edges, &predecessors) {
+            let mut key = cycle.clone();
+            key.sort_unstable();
+
+            if known_cycles.insert(key) {
+                cycles.push(
+                    cycle
+                        .into_iter()
+                        .map(|i| (edges[i].token_in, edges[i].pair))
+                        .collect(),
+                );
+            }
+        }
+
+        if !is_relaxed {
+            break;
+        }
+    }
+
+    cycles
+}
+
+/// Return cycles of the predecessor graph as lists of edge indices in the
+/// order of the trade.
+fn predecessor_cycles(edges: &[SwapEdge], predecessors: &HashMap<i32, usize>) -> Vec<Vec<usize>> {
+    // Index of the walk each token was visited by.
+    let mut visited: HashMap<i32, usize> = HashMap::new();
+    let mut cycles = Vec::new();
+
+    for (walk, start) in predecessors.keys().enumerate() {
+        let mut token = *start;
+
+        loop {
+            match visited.get(&token) {
+                Some(&visited_by) if visited_by == walk => {
+                    let mut cycle = Vec::new();
+                    let mut cycle_token = token;
+                    loop {
+                        let edge = predecessors[&cycle_token];
+                        cycle.push(edge);
+                        cycle_token = edges[edge].token_in;
+
+                        if cycle_token == token {
+                            break;
+                        }
+                    }
+
+                    cycle.reverse();
+                    cycles.push(cycle);
+                    break;
+                }
+                // The rest of the walk was checked by a previous one.
+                Some(_) => break,
+                None => {}
+            }
+
+            visited.insert(token, walk);
+
+            match predecessors.get(&token) {
+                Some(&edge) => token = edges[edge].token_in,
+                None => break,
+            }
+        }
+    }
+
+    cycles
+}
+
+/// Build the route of the cycle, starting and ending at the `base` token if
+/// the cycle goes through it, otherwise at its first token.
+fn cycle_route(cycle: &[(i32, &PairEdge)], base: i32) -> Route {
+    let start = cycle
+        .iter()
+        .position(|(token_in, _)| *token_in == base)
+        .unwrap_or(0);
+
+    let mut route = Route::new(cycle[start].0, U256::zero());
+    for hop in 1..=cycle.len() {
+        let (_, pair) = cycle[(start + hop - 1) % cycle.len()];
+        let (token_out, _) = cycle[(start + hop) % cycle.len()];
+
+        route = route.extend(token_out, pair, U256::zero());
+    }
+
+    route
+}
+
+/// Find the input amount of the cycle giving the biggest profit, or `None` if
+/// the cycle isn't profitable with the integer swap math.
+///
+/// The profit is a concave function of the input, so it's maximized by the
+/// ternary search between one unit and the reserve of the first pair, above
+/// which the output drops quickly.
+fn size_cycle(mut route: Route) -> Option<Opportunity> {
+    let output = |amount: U256| route.quote(amount).unwrap_or_default();
+    // Compares `output(a) - a < output(b) - b` without negative values.
+    let is_less_profitable = |a: U256, b: U256| output(a) + b < output(b) + a;
+
+    let mut low = U256::one();
+    let (mut high, _) = route.pairs[0].reserves_from(route.tokens[0]);
+
+    while high - low > U256::from(2) {
+        let third = (high - low) / 3;
+        let (left, right) = (low + third, high - third);
+
+        if is_less_profitable(left, right) {
+            low = left + 1;
+        } else {
+            high = right;
+        }
+    }
+
+    let mut amount_in = low;
+    let mut candidate = low + 1;
+    while candidate <= high {
+        if is_less_profitable(amount_in, candidate) {
+            amount_in = candidate;
+        }
+        candidate += U256::one();
+    }
+
+    let amount_out = route.quote(amount_in)?;
+    if amount_out <= amount_in {
+        return None;
+    }
+
+    route.amount_in = amount_in;
+    route.amount_out = amount_out;
+
+    Some(Opportunity {
+        profit: amount_out - amount_in,
+        route,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing;
+
+    use super::*;
+
+    const DEEP: u128 = 1_000_000_000;
+
+    fn profit(route: &Route, amount: U256) -> U256 {
+        route
+            .quote(amount)
+            .unwrap_or_default()
+            .saturating_sub(amount)
+    }
+
+    #[test]
+    fn test_profitable_triangle() {
+        // Token 3 is sold for twice as much of token 1 as it costs.
+        let graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 2, 3, DEEP, DEEP),
+            (3, 3, 1, DEEP, 2 * DEEP),
+        ]);
+
+        let cycles = find_negative_cycles(&graph, 1, 10);
+        assert_eq!(cycles.len(), 1);
+
+        let route = cycle_route(&cycles[0], 1);
+        assert_eq!(route.tokens, vec![1, 2, 3, 1]);
+        assert_eq!(
+            route.pairs.iter().map(|pair| pair.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let Opportunity {
+            route,
+            profit: best,
+        } = size_cycle(route).expect("cycle is profitable");
+        assert_eq!(route.quote(route.amount_in), Some(route.amount_out));
+        assert_eq!(best, route.amount_out - route.amount_in);
+
+        // The profit is the biggest around the optimal input.
+        for amount in [
+            route.amount_in / 2,
+            route.amount_in - 1,
+            route.amount_in + 1,
+            route.amount_in * 2,
+        ] {
+            assert!(
+                profit(&route, amount) <= best,
+                "input {} is more profitable",
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn test_balanced_triangle() {
+        let graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 2, 3, DEEP, DEEP),
+            (3, 3, 1, DEEP, DEEP),
+        ]);
+
+        assert!(find_negative_cycles(&graph, 1, 10).is_empty());
+    }
+
+    #[test]
+    fn test_fee_eats_small_mispricing() {
+        // The cycle gains 0.1% before the fees, which take about 0.9%.
+        let graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 2, 3, DEEP, DEEP),
+            (3, 3, 1, DEEP, DEEP + DEEP / 1000),
+        ]);
+
+        assert!(find_negative_cycles(&graph, 1, 10).is_empty());
+    }
+}
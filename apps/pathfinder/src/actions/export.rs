@@ -0,0 +1,397 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use clap::ValueEnum;
+use color_eyre::eyre;
+use config::Config;
+use database::{tokens::TokenEntry, DB};
+
+use super::find::{get_token, PairEdge, PairsGraph};
+use crate::{
+    cli::ExportArgs,
+    filters::{pair_liquidity, GraphFilter},
+    graph::PairGraph,
+    pricing,
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Dot,
+    Graphml,
+    Csv,
+}
+
+/// Pair selected for the export with the tokens it connects.
+struct ExportedPair<'a> {
+    token0: i32,
+    token1: i32,
+    pair: &'a PairEdge,
+}
+
+/// Export the pairs graph with token symbols as node labels and reserves as
+/// edge labels.
+pub async fn export(config: Config, args: ExportArgs) -> eyre::Result<()> {
+    let database = DB::from_url(&config.database.url).await?;
+
+    let mut txn = database.pool().begin().await?;
+
+    let base_token = args.base_token.or(config.pathfinder.base_token);
+
+    let filter = GraphFilter::load(&mut txn, &config.pathfinder.filters, base_token).await?;
+    let pair_graph = PairGraph::load(&mut txn, filter, None).await?;
+    let graph = pair_graph.graph();
+
+    let tokens: HashMap<i32, TokenEntry> = DB::tokens(&mut txn)
+        .await?
+        .into_iter()
+        .map(|token| (token.id, token))
+        .collect();
+
+    let mut pairs: Vec<ExportedPair> = graph
+        .all_edges()
+        .flat_map(|(token_a, token_b, pairs)| {
+            pairs.iter().map(move |pair| {
+                let token1 = if pair.token0_id == token_a {
+                    token_b
+                } else {
+                    token_a
+                };
+
+                ExportedPair {
+                    token0: pair.token0_id,
+                    token1,
+                    pair,
+                }
+            })
+        })
+        .collect();
+
+    if let Some(min_liquidity) = args.min_liquidity {
+        let base_token =
+            base_token.ok_or_else(|| eyre::eyre!("Base token is required to measure liquidity"))?;
+        let base_token = get_token(&mut txn, base_token).await?;
+
+        let decimals = pricing::known_decimals(tokens.values());
+
+        let prices = pricing::price_tokens(graph, &decimals, base_token.id, &[]);
+
+        // Pairs with tokens which couldn't be priced are dropped.
+        pairs.retain(|exported| {
+            pair_liquidity(
+                exported.pair,
+                exported.token0,
+                exported.token1,
+                &prices,
+                &decimals,
+            )
+            .map_or(false, |liquidity| liquidity >= min_liquidity)
+        });
+    }
+
+    if let Some(center) = args.around {
+        let center = get_token(&mut txn, center).await?;
+        let neighborhood = neighborhood(graph, &pairs, center.id, args.hops);
+
+        pairs.retain(|exported| {
+            neighborhood.contains(&exported.token0) && neighborhood.contains(&exported.token1)
+        });
+    }
+
+    let writer: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = BufWriter::new(writer);
+
+    let symbol = |token: i32| {
+        tokens
+            .get(&token)
+            .map_or_else(|| token.to_string(), |token| token.symbol.clone())
+    };
+
+    match args.format {
+        ExportFormat::Dot => write_dot(&mut writer, &pairs, symbol)?,
+        ExportFormat::Graphml => write_graphml(&mut writer, &pairs, symbol)?,
+        ExportFormat::Csv => write_csv(&mut writer, &pairs, symbol)?,
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Return tokens reachable from the `center` in at most `hops` swaps through
+/// the given pairs.
+fn neighborhood(
+    graph: &PairsGraph,
+    pairs: &[ExportedPair],
+    center: i32,
+    hops: usize,
+) -> HashSet<i32> {
+    let selected: HashSet<&str> = pairs
+        .iter()
+        .map(|exported| exported.pair.address.as_str())
+        .collect();
+
+    let mut reached = HashSet::from([center]);
+    let mut queue = VecDeque::from([(center, 0)]);
+
+    while let Some((token, distance)) = queue.pop_front() {
+        if distance == hops {
+            continue;
+        }
+
+        for (_, neighbor, edge_pairs) in graph.edges(token) {
+            let is_selected = edge_pairs
+                .iter()
+                .any(|pair| selected.contains(pair.address.as_str()));
+
+            if is_selected && reached.insert(neighbor) {
+                queue.push_back((neighbor, distance + 1));
+            }
+        }
+    }
+
+    reached
+}
+
+/// Tokens of the pairs sorted by id, so the output is stable.
+fn exported_tokens(pairs: &[ExportedPair]) -> Vec<i32> {
+    let mut tokens: Vec<i32> = pairs
+        .iter()
+        .flat_map(|exported| [exported.token0, exported.token1])
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    tokens.sort_unstable();
+
+    tokens
+}
+
+fn write_dot(
+    writer: &mut impl Write,
+    pairs: &[ExportedPair],
+    symbol: impl Fn(i32) -> String,
+) -> io::Result<()> {
+    let escape = |value: String| value.replace('\\', "\\\\").replace('"', "\\\"");
+
+    writeln!(writer, "graph pairs {{")?;
+
+    for token in exported_tokens(pairs) {
+        writeln!(
+            writer,
+            "    {} [label=\"{}\"];",
+            token,
+            escape(symbol(token))
+        )?;
+    }
+
+    for exported in pairs {
+        writeln!(
+            writer,
+            "    {} -- {} [label=\"{} / {}\", pair=\"{}\"];",
+            exported.token0,
+            exported.token1,
+            exported.pair.reserve0,
+            exported.pair.reserve1,
+            exported.pair.address.trim_end(),
+        )?;
+    }
+
+    writeln!(writer, "}}")
+}
+
+fn write_graphml(
+    writer: &mut impl Write,
+    pairs: &[ExportedPair],
+    symbol: impl Fn(i32) -> String,
+) -> io::Result<()> {
+    let escape = |value: String| {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="symbol" for="node" attr.name="symbol" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="pair" for="edge" attr.name="pair" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="reserve0" for="edge" attr.name="reserve0" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="reserve1" for="edge" attr.name="reserve1" attr.type="string"/>"#
+    )?;
+    writeln!(writer, r#"  <graph id="pairs" edgedefault="undirected">"#)?;
+
+    for token in exported_tokens(pairs) {
+        writeln!(writer, r#"    <node id="{}">"#, token)?;
+        writeln!(
+            writer,
+            r#"      <data key="symbol">{}</data>"#,
+            escape(symbol(token))
+        )?;
+        writeln!(writer, r#"    </node>"#)?;
+    }
+
+    for exported in pairs {
+        writeln!(
+            writer,
+            r#"    <edge source="{}" target="{}">"#,
+            exported.token0, exported.token1
+        )?;
+        writeln!(
+            writer,
+            r#"      <data key="pair">{}</data>"#,
+            exported.pair.address.trim_end()
+        )?;
+        writeln!(
+            writer,
+            r#"      <data key="reserve0">{}</data>"#,
+            exported.pair.reserve0
+        )?;
+        writeln!(
+            writer,
+            r#"      <data key="reserve1">{}</data>"#,
+            exported.pair.reserve1
+        )?;
+        writeln!(writer, r#"    </edge>"#)?;
+    }
+
+    writeln!(writer, r#"  </graph>"#)?;
+    writeln!(writer, r#"</graphml>"#)
+}
+
+fn write_csv(
+    writer: &mut impl Write,
+    pairs: &[ExportedPair],
+    symbol: impl Fn(i32) -> String,
+) -> io::Result<()> {
+    let escape = |value: String| {
+        if value.contains([',', '"', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value
+        }
+    };
+
+    writeln!(writer, "symbol0,symbol1,pair,reserve0,reserve1")?;
+
+    for exported in pairs {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            escape(symbol(exported.token0)),
+            escape(symbol(exported.token1)),
+            exported.pair.address.trim_end(),
+            exported.pair.reserve0,
+            exported.pair.reserve1,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing;
+
+    use super::*;
+
+    fn export(
+        write: impl Fn(&mut Vec<u8>, &[ExportedPair], &dyn Fn(i32) -> String) -> io::Result<()>,
+    ) -> String {
+        let pairs = [testing::pair(1, 1, 10, 20), testing::pair(2, 2, 30, 40)];
+        let exported = [
+            ExportedPair {
+                token0: 1,
+                token1: 2,
+                pair: &pairs[0],
+            },
+            ExportedPair {
+                token0: 2,
+                token1: 3,
+                pair: &pairs[1],
+            },
+        ];
+
+        let symbol = |token: i32| match token {
+            1 => r#"a"b"#.to_string(),
+            2 => "c,d".to_string(),
+            _ => "<&>".to_string(),
+        };
+
+        let mut output = Vec::new();
+        write(&mut output, &exported, &symbol).expect("writing to memory doesn't fail");
+
+        String::from_utf8(output).expect("output is UTF-8")
+    }
+
+    #[test]
+    fn test_write_dot() {
+        let output = export(|writer, pairs, symbol| write_dot(writer, pairs, symbol));
+
+        assert_eq!(
+            output,
+            format!(
+                concat!(
+                    "graph pairs {{\n",
+                    "    1 [label=\"a\\\"b\"];\n",
+                    "    2 [label=\"c,d\"];\n",
+                    "    3 [label=\"<&>\"];\n",
+                    "    1 -- 2 [label=\"10 / 20\", pair=\"{}\"];\n",
+                    "    2 -- 3 [label=\"30 / 40\", pair=\"{}\"];\n",
+                    "}}\n",
+                ),
+                testing::address(1),
+                testing::address(2),
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_graphml() {
+        let output = export(|writer, pairs, symbol| write_graphml(writer, pairs, symbol));
+
+        assert!(output.contains(r#"<data key="symbol">a&quot;b</data>"#));
+        assert!(output.contains(r#"<data key="symbol">c,d</data>"#));
+        assert!(output.contains(r#"<data key="symbol">&lt;&amp;&gt;</data>"#));
+        assert!(output.contains(r#"<edge source="2" target="3">"#));
+        assert!(output.contains(&format!(
+            r#"<data key="pair">{}</data>"#,
+            testing::address(2)
+        )));
+        assert!(output.contains(r#"<data key="reserve1">40</data>"#));
+        assert!(output.ends_with("</graphml>\n"));
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let output = export(|writer, pairs, symbol| write_csv(writer, pairs, symbol));
+
+        assert_eq!(
+            output,
+            format!(
+                "symbol0,symbol1,pair,reserve0,reserve1\n\"a\"\"b\",\"c,d\",{},10,20\n\"c,d\",<&>,{},30,40\n",
+                testing::address(1),
+                testing::address(2),
+            )
+        );
+    }
+}
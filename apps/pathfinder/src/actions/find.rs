@@ -1,89 +1,892 @@
-use std::collections::HashMap;
-
-use color_eyre::eyre::{self, Context};
-use config::Config;
-use database::{tokens::TokenEntry, PairsStreamEntry, DB};
-use ethers::{abi::Address, providers::StreamExt};
-use petgraph::{
-    prelude::{GraphMap, UnGraphMap},
-    Undirected,
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
 };
-use sqlx::{types::BigDecimal, PgConnection};
 
-use crate::cli::FindArgs;
+use color_eyre::eyre;
+use config::{Config, Fee};
+use database::{reserves::decimal_to_reserve, tokens::TokenEntry, PairsStreamEntry, DB};
+use ethers::{abi::Address, types::U256};
+use petgraph::{prelude::GraphMap, Undirected};
+use sqlx::PgConnection;
+
+use crate::{
+    calldata::{self, SwapAmounts},
+    cli::FindArgs,
+    filters::GraphFilter,
+    graph::PairGraph,
+    math,
+};
 
 pub type ShortAddress = String;
 
-pub struct BaseTokenInfo {
-    pub address: Address,
-    pub reserves: HashMap<ShortAddress, (BigDecimal, BigDecimal)>,
+/// Maximum number of swaps in a single route.
+pub(crate) const MAX_HOPS: usize = 4;
+
+/// Graph of tokens connected by the pairs of all the factories. As several
+/// DEXes could have a pair for the same tokens, each edge holds all of them.
+pub type PairsGraph = GraphMap<i32, Vec<PairEdge>, Undirected>;
+
+/// Pair of the pairs graph with its latest reserves.
+#[derive(Debug, Clone)]
+pub struct PairEdge {
+    /// Id of the pair in the database.
+    pub id: i32,
+
+    /// Address of the pair.
+    pub address: ShortAddress,
+
+    /// Id of the factory the pair belongs to.
+    pub factory_id: i32,
+
+    /// Swap fee of the pair.
+    pub fee: Fee,
+
+    /// Id of the token0 of the pair, used to orient reserves.
+    pub token0_id: i32,
+
+    /// Amount of token0 in the pair.
+    pub reserve0: U256,
+
+    /// Amount of token1 in the pair.
+    pub reserve1: U256,
+}
+
+impl PairEdge {
+    /// Build the edge from the stored pair, return `None` if its reserves
+    /// are invalid.
+    pub(crate) fn from_entry(pair: &PairsStreamEntry) -> Option<Self> {
+        let reserve0 = decimal_to_reserve(&pair.reserve0)?;
+        let reserve1 = decimal_to_reserve(&pair.reserve1)?;
+
+        Some(Self {
+            id: pair.pair_id,
+            address: pair.pair_address.clone(),
+            factory_id: pair.factory_id,
+            fee: Fee {
+                numerator: pair.fee_numerator as u32,
+                denominator: pair.fee_denominator as u32,
+            },
+            token0_id: pair.token0_id,
+            reserve0: U256::from(reserve0),
+            reserve1: U256::from(reserve1),
+        })
+    }
+
+    /// Return reserves of the pair as `(reserve_in, reserve_out)` for a swap
+    /// from `token_in`.
+    pub(crate) fn reserves_from(&self, token_in: i32) -> (U256, U256) {
+        if token_in == self.token0_id {
+            (self.reserve0, self.reserve1)
+        } else {
+            (self.reserve1, self.reserve0)
+        }
+    }
+
+    /// Update reserves of the pair after a swap from `token_in`.
+    fn apply_swap(&mut self, token_in: i32, amount_in: U256, amount_out: U256) {
+        if token_in == self.token0_id {
+            self.reserve0 += amount_in;
+            self.reserve1 -= amount_out;
+        } else {
+            self.reserve1 += amount_in;
+            self.reserve0 -= amount_out;
+        }
+    }
+}
+
+/// Sequence of swaps from the first token to the last one.
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// Ids of the tokens on the route, including the start and the goal.
+    pub tokens: Vec<i32>,
+
+    /// Pairs used for each hop.
+    pub pairs: Vec<PairEdge>,
+
+    /// Amount of the first token put in at the start of the route.
+    pub amount_in: U256,
+
+    /// Amount of the last token received at the end of the route.
+    pub amount_out: U256,
+}
+
+impl Route {
+    pub(crate) fn new(token: i32, amount: U256) -> Self {
+        Self {
+            tokens: vec![token],
+            pairs: Vec::new(),
+            amount_in: amount,
+            amount_out: amount,
+        }
+    }
+
+    fn first_token(&self) -> i32 {
+        *self.tokens.first().expect("route always has a start token")
+    }
+
+    fn last_token(&self) -> i32 {
+        *self.tokens.last().expect("route always has a start token")
+    }
+
+    /// Return a copy of the route extended with one more swap.
+    pub(crate) fn extend(&self, token: i32, pair: &PairEdge, amount_out: U256) -> Self {
+        let mut route = self.clone();
+        route.tokens.push(token);
+        route.pairs.push(pair.clone());
+        route.amount_out = amount_out;
+        route
+    }
+
+    /// Return the output of the route for the given input amount, or `None`
+    /// if any of the swaps reverts.
+    pub(crate) fn quote(&self, amount_in: U256) -> Option<U256> {
+        self.pairs
+            .iter()
+            .zip(&self.tokens)
+            .try_fold(amount_in, |amount, (pair, token_in)| {
+                let (reserve_in, reserve_out) = pair.reserves_from(*token_in);
+                math::get_amount_out(amount, reserve_in, reserve_out, pair.fee)
+            })
+    }
+
+    /// Return the amounts of the tokens on the route as the swaps give them,
+    /// from the input to the output.
+    pub(crate) fn amounts(&self) -> Vec<U256> {
+        let mut amounts = vec![self.amount_in];
+
+        for (pair, token_in) in self.pairs.iter().zip(&self.tokens) {
+            let amount_in = *amounts.last().expect("amounts start with the input");
+            let (reserve_in, reserve_out) = pair.reserves_from(*token_in);
+            let amount_out = math::get_amount_out(amount_in, reserve_in, reserve_out, pair.fee)
+                .expect("route swaps are valid");
+
+            amounts.push(amount_out);
+        }
+
+        amounts
+    }
+
+    /// Return the price of the first token in the last one in their smallest
+    /// units from the reserves of the pairs, without the fees.
+    pub(crate) fn mid_price(&self) -> f64 {
+        self.pairs
+            .iter()
+            .zip(&self.tokens)
+            .map(|(pair, token_in)| {
+                let (reserve_in, reserve_out) = pair.reserves_from(*token_in);
+
+                math::to_f64(reserve_out) / math::to_f64(reserve_in)
+            })
+            .product()
+    }
+
+    /// Return the price impact of each swap of the route.
+    pub(crate) fn hop_price_impacts(&self) -> Vec<f64> {
+        self.pairs
+            .iter()
+            .zip(&self.tokens)
+            .zip(self.amounts().windows(2))
+            .map(|((pair, token_in), amounts)| {
+                let (reserve_in, reserve_out) = pair.reserves_from(*token_in);
+
+                math::price_impact(
+                    amounts[0],
+                    amounts[1],
+                    math::spot_rate(reserve_in, reserve_out, pair.fee),
+                )
+            })
+            .collect()
+    }
+
+    /// Return the output for a unit of input of an infinitely small swap
+    /// along the route: the product of the reserve ratios of the pairs with
+    /// their fees.
+    pub(crate) fn spot_rate(&self) -> f64 {
+        self.pairs
+            .iter()
+            .zip(&self.tokens)
+            .map(|(pair, token_in)| {
+                let (reserve_in, reserve_out) = pair.reserves_from(*token_in);
+
+                math::spot_rate(reserve_in, reserve_out, pair.fee)
+            })
+            .product()
+    }
+
+    /// Return the share of the output lost to the reserves moved by the swaps
+    /// compared to the spot rate. Fees aren't counted as the impact.
+    pub(crate) fn price_impact(&self) -> f64 {
+        math::price_impact(self.amount_in, self.amount_out, self.spot_rate())
+    }
+
+    /// Check if both routes go through the same pairs.
+    fn has_same_pairs(&self, other: &Route) -> bool {
+        self.pairs
+            .iter()
+            .map(|pair| &pair.address)
+            .eq(other.pairs.iter().map(|pair| &pair.address))
+    }
+
+    /// Return a copy of the route with one more swap before its start.
+    fn prepend(&self, token: i32, pair: &PairEdge, amount_in: U256) -> Self {
+        let mut route = self.clone();
+        route.tokens.insert(0, token);
+        route.pairs.insert(0, pair.clone());
+        route.amount_in = amount_in;
+        route
+    }
 }
 
 pub async fn find(config: Config, args: FindArgs) -> eyre::Result<()> {
     let database = DB::from_url(&config.database.url).await?;
 
-    let mut graph = UnGraphMap::new();
-
     let mut txn = database.pool().begin().await?;
 
-    fill_graph_from_db(&mut txn, &mut graph, args.base_token).await?;
+    let at_block = args.at_block.map(|block| block as i64);
+
+    let base_token = args.base_token.or(config.pathfinder.base_token);
+    let filter = GraphFilter::load(&mut txn, &config.pathfinder.filters, base_token).await?;
+    let mut graph = PairGraph::load(&mut txn, filter, at_block)
+        .await?
+        .into_graph();
 
     let start_token = get_token(&mut txn, args.from).await?;
     let goal_token = get_token(&mut txn, args.to).await?;
 
-    petgraph::algo::dijkstra(
-        &graph,
-        start_token.id,
-        Some(goal_token.id),
-        |(node_a, node_b, (reserve0, reserve1))| todo!(),
+    if start_token.id == goal_token.id {
+        eyre::bail!("Start and goal tokens are the same");
+    }
+
+    for address in &args.avoid {
+        remove_avoided(&mut txn, &mut graph, *address).await?;
+    }
+
+    let mut via = Vec::with_capacity(args.via.len());
+    for address in &args.via {
+        let token = get_token(&mut txn, *address).await?;
+        if [start_token.id, goal_token.id].contains(&token.id) {
+            eyre::bail!(
+                "Route can't pass through its start or goal token {}",
+                token.symbol
+            );
+        }
+
+        via.push(token.id);
+    }
+
+    let max_hops = args.max_hops as usize;
+    if via.len() >= max_hops {
+        eyre::bail!(
+            "Route through {} tokens needs more than {} hops",
+            via.len(),
+            max_hops
+        );
+    }
+
+    let factory_names = factory_names(&mut txn).await?;
+
+    let no_route = || {
+        eyre::eyre!(
+            "No route found from {} to {}",
+            start_token.symbol,
+            goal_token.symbol
+        )
+    };
+
+    if let Some(parts) = args.split {
+        let routes = find_split_routes(
+            &graph,
+            start_token.id,
+            goal_token.id,
+            args.value,
+            parts,
+            max_hops,
+        )
+        .ok_or_else(no_route)?;
+        let single_route =
+            find_best_route(&graph, start_token.id, goal_token.id, args.value, max_hops);
+
+        println!("Split into {} routes", routes.len());
+        for route in &routes {
+            print_route(&mut txn, route, &factory_names).await?;
+            println!(
+                "  Input: {} {} ({:.2}%), output: {} {}",
+                route.amount_in,
+                start_token.symbol,
+                (route.amount_in * U256::from(10_000) / args.value).as_u64() as f64 / 100.0,
+                route.amount_out,
+                goal_token.symbol,
+            );
+        }
+
+        let total_out = routes
+            .iter()
+            .fold(U256::zero(), |total, route| total + route.amount_out);
+        println!("Expected output: {} {}", total_out, goal_token.symbol);
+        if let Some(single_route) = single_route {
+            println!(
+                "Best single route output: {} {}",
+                single_route.amount_out, goal_token.symbol
+            );
+        }
+
+        return Ok(());
+    }
+
+    if let Some(count) = args.top {
+        let routes = find_top_routes_via(
+            &graph,
+            start_token.id,
+            &via,
+            goal_token.id,
+            args.value,
+            count as usize,
+            max_hops,
+        );
+        if routes.is_empty() {
+            return Err(no_route());
+        }
+
+        for (i, route) in routes.iter().enumerate() {
+            println!("#{}", i + 1);
+            print_route(&mut txn, route, &factory_names).await?;
+            println!(
+                "  Output: {} {}, price impact: {:.2}%, hops: {}",
+                route.amount_out,
+                goal_token.symbol,
+                route.price_impact() * 100.0,
+                route.pairs.len(),
+            );
+        }
+
+        return Ok(());
+    }
+
+    let route = if args.exact_out {
+        find_best_route_exact_out(&graph, start_token.id, goal_token.id, args.value, max_hops)
+    } else {
+        find_top_routes_via(
+            &graph,
+            start_token.id,
+            &via,
+            goal_token.id,
+            args.value,
+            1,
+            max_hops,
+        )
+        .pop()
+    }
+    .ok_or_else(no_route)?;
+
+    if args.calldata {
+        let recipient = args.recipient.expect("calldata requires the recipient");
+
+        let amounts = if args.exact_out {
+            SwapAmounts::ExactOutput {
+                amount_out: route.amount_out,
+                amount_in_max: math::max_amount_in(route.amount_in, args.slippage_bps)
+                    .ok_or_else(|| eyre::eyre!("Maximum input overflows"))?,
+            }
+        } else {
+            SwapAmounts::ExactInput {
+                amount_in: route.amount_in,
+                amount_out_min: math::min_amount_out(route.amount_out, args.slippage_bps),
+            }
+        };
+
+        let router = route_router(&mut txn, &config, &route).await?;
+
+        let mut path = Vec::with_capacity(route.tokens.len());
+        for token_id in &route.tokens {
+            let token = get_token_by_id(&mut txn, *token_id).await?;
+            path.push(Address::from_str(token.address.trim_end())?);
+        }
+
+        let deadline = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + args.deadline;
+        let transaction = calldata::swap_transaction(router, amounts, path, recipient, deadline);
+
+        println!("{}", serde_json::to_string_pretty(&transaction)?);
+
+        return Ok(());
+    }
+
+    print_route(&mut txn, &route, &factory_names).await?;
+    if args.exact_out {
+        println!("Required input: {} {}", route.amount_in, start_token.symbol);
+    } else {
+        println!(
+            "Expected output: {} {}",
+            route.amount_out, goal_token.symbol
+        );
+    }
+
+    // Prices of a whole start token in whole goal tokens.
+    let decimals_scale = 10f64.powi(start_token.decimals - goal_token.decimals);
+    let execution_price = math::to_f64(route.amount_out) / math::to_f64(route.amount_in);
+
+    println!(
+        "Mid price: 1 {} = {} {}",
+        start_token.symbol,
+        route.mid_price() * decimals_scale,
+        goal_token.symbol
     );
+    println!(
+        "Execution price: 1 {} = {} {}",
+        start_token.symbol,
+        execution_price * decimals_scale,
+        goal_token.symbol
+    );
+
+    let hop_impacts: Vec<String> = route
+        .hop_price_impacts()
+        .iter()
+        .enumerate()
+        .map(|(i, impact)| format!("{}. {:.2}%", i + 1, impact * 100.0))
+        .collect();
+    println!(
+        "Price impact: {:.2}% ({})",
+        route.price_impact() * 100.0,
+        hop_impacts.join(", ")
+    );
+
+    let slippage = args.slippage_bps as f64 / 100.0;
+    if args.exact_out {
+        let max_amount_in = math::max_amount_in(route.amount_in, args.slippage_bps)
+            .ok_or_else(|| eyre::eyre!("Maximum input overflows"))?;
+
+        println!(
+            "Maximum input with {:.2}% slippage: {} {}",
+            slippage, max_amount_in, start_token.symbol
+        );
+    } else {
+        println!(
+            "Minimum output with {:.2}% slippage: {} {}",
+            slippage,
+            math::min_amount_out(route.amount_out, args.slippage_bps),
+            goal_token.symbol
+        );
+    }
 
     Ok(())
 }
 
-async fn fill_graph_from_db(
+/// Remove the token with all its pairs, or the pair with the given address
+/// from the graph.
+async fn remove_avoided(
     conn: &mut PgConnection,
-    graph: &mut GraphMap<i32, (BigDecimal, BigDecimal), Undirected>,
-    base_token: Address,
-) -> eyre::Result<BaseTokenInfo> {
-    let mut pairs_stream = DB::pairs_stream(conn).await?;
-
-    let base_token_info = BaseTokenInfo {
-        address: base_token,
-        reserves: HashMap::new(),
-    };
+    graph: &mut PairsGraph,
+    address: Address,
+) -> eyre::Result<()> {
+    if let Some(token) = DB::token_by_address(&mut *conn, address).await? {
+        graph.remove_node(token.id);
+        return Ok(());
+    }
 
-    let base_token_short_address = base_token.to_string();
+    let pair = DB::pair_by_address(conn, address)
+        .await?
+        .ok_or_else(|| eyre::eyre!("Neither token nor pair found {:?}", address))?;
 
-    while let Some(result) = pairs_stream.next().await {
-        let pair = result.wrap_err("Failed to get pair from database")?;
+    remove_pair(graph, pair.token0, pair.token1, pair.id);
 
-        if [pair.token0_short_address, pair.token1_short_address].contains(&base_token_short_address) {
-            let token0 = get_token_by_id(conn, pair.token0_id).await?;
-            let token1 = get_token_by_id(conn, pair.token1_id).await?;
+    Ok(())
+}
 
-            let (reserve0, reserve1, address) = if pair.token0_short_address == base_token_short_address {
-                (pair.reserve0, pair.reserve1, token1.address)
-            } else {
-                (pair.reserve1, pair.reserve0, token0.address)
-            };
+/// Remove the pair from the edge of its tokens, and the edge if it was the
+/// only pair of the tokens.
+fn remove_pair(graph: &mut PairsGraph, token0: i32, token1: i32, pair_id: i32) {
+    if let Some(pairs) = graph.edge_weight_mut(token0, token1) {
+        pairs.retain(|edge| edge.id != pair_id);
 
-            base_token_info
-                .reserves
-                .insert(address, (reserve0, reserve1));
+        if pairs.is_empty() {
+            graph.remove_edge(token0, token1);
         }
+    }
+}
+
+/// Return the router of the factory all the pairs of the route belong to, as
+/// a router swaps only through the pairs of its factory.
+async fn route_router(
+    conn: &mut PgConnection,
+    config: &Config,
+    route: &Route,
+) -> eyre::Result<Address> {
+    let factory_id = route.pairs[0].factory_id;
+    if route.pairs.iter().any(|pair| pair.factory_id != factory_id) {
+        eyre::bail!("Route goes through the pairs of several factories, a single router can't swap along it");
+    }
+
+    let factory = DB::factories(conn)
+        .await?
+        .into_iter()
+        .find(|factory| factory.id == factory_id)
+        .ok_or_else(|| eyre::eyre!("Factory not found with id: {}", factory_id))?;
+
+    config
+        .factories
+        .iter()
+        .find(|configured| {
+            database::format_address(configured.address) == factory.address.trim_end()
+        })
+        .and_then(|configured| configured.router)
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "Router of the factory {} isn't set in the config",
+                factory.address.trim_end()
+            )
+        })
+}
+
+/// Return names of the factories by their ids, or their addresses for the
+/// factories without a name.
+pub(crate) async fn factory_names(conn: &mut PgConnection) -> eyre::Result<HashMap<i32, String>> {
+    let names = DB::factories(conn)
+        .await?
+        .into_iter()
+        .map(|factory| {
+            let name = factory
+                .name
+                .unwrap_or_else(|| factory.address.trim_end().to_string());
 
-        graph.add_edge(
-            pair.token0_id,
-            pair.token1_id,
-            (pair.reserve0, pair.reserve1),
+            (factory.id, name)
+        })
+        .collect();
+
+    Ok(names)
+}
+
+/// Print the tokens of the route and the pairs of each hop.
+pub(crate) async fn print_route(
+    conn: &mut PgConnection,
+    route: &Route,
+    factory_names: &HashMap<i32, String>,
+) -> eyre::Result<()> {
+    let mut symbols = Vec::with_capacity(route.tokens.len());
+    for token_id in &route.tokens {
+        symbols.push(get_token_by_id(&mut *conn, *token_id).await?.symbol);
+    }
+
+    println!("Route: {}", symbols.join(" -> "));
+    for (i, pair) in route.pairs.iter().enumerate() {
+        println!(
+            "  {}. {} -> {} via {} ({})",
+            i + 1,
+            symbols[i],
+            symbols[i + 1],
+            pair.address.trim_end(),
+            factory_names
+                .get(&pair.factory_id)
+                .map_or("unknown", String::as_str),
         );
     }
 
     Ok(())
 }
 
-async fn get_token(
+/// Find the route from `from` to `to` which gives the biggest output for the
+/// given input `amount`.
+///
+/// As the output of each swap depends on the amount put in, edge weights are
+/// not static, so instead of Dijkstra a Bellman-Ford like relaxation is used:
+/// on each iteration routes are extended by one hop, keeping only the one with
+/// the biggest amount for each reached token. Routes never visit the same
+/// token twice.
+pub(crate) fn find_best_route(
+    graph: &PairsGraph,
+    from: i32,
+    to: i32,
+    amount: U256,
+    max_hops: usize,
+) -> Option<Route> {
+    find_top_routes(graph, from, to, amount, 1, max_hops).pop()
+}
+
+/// Find up to `count` distinct routes from `from` to `to` with the biggest
+/// outputs for the given input `amount`, from the best one.
+///
+/// Same relaxation as [`find_best_route`], but `count` routes with the
+/// biggest amounts are kept for each reached token, so the routes differ in
+/// at least one pair.
+pub(crate) fn find_top_routes(
+    graph: &PairsGraph,
+    from: i32,
+    to: i32,
+    amount: U256,
+    count: usize,
+    max_hops: usize,
+) -> Vec<Route> {
+    extend_top_routes(graph, &Route::new(from, amount), to, count, max_hops)
+}
+
+/// Find up to `count` best routes which continue the given one to `to` with
+/// at most `max_hops` more swaps. Tokens of the given route aren't visited
+/// again.
+fn extend_top_routes(
+    graph: &PairsGraph,
+    start: &Route,
+    to: i32,
+    count: usize,
+    max_hops: usize,
+) -> Vec<Route> {
+    let mut frontier = HashMap::from([(start.last_token(), vec![start.clone()])]);
+    let mut found: Vec<Route> = Vec::new();
+
+    for _ in 0..max_hops {
+        let mut next: HashMap<i32, Vec<Route>> = HashMap::new();
+
+        for route in frontier.values().flatten() {
+            let token_in = route.last_token();
+
+            for (_, token_out, pairs) in graph.edges(token_in) {
+                if route.tokens.contains(&token_out) {
+                    continue;
+                }
+
+                for pair in pairs {
+                    let (reserve_in, reserve_out) = pair.reserves_from(token_in);
+                    let Some(amount_out) =
+                        math::get_amount_out(route.amount_out, reserve_in, reserve_out, pair.fee)
+                    else {
+                        continue;
+                    };
+
+                    let known = next.entry(token_out).or_default();
+
+                    // Routes are kept sorted from the biggest output.
+                    let position = known.partition_point(|known| known.amount_out >= amount_out);
+                    if position < count {
+                        known.insert(position, route.extend(token_out, pair, amount_out));
+                        known.truncate(count);
+                    }
+                }
+            }
+        }
+
+        // Routes that reached the goal are not extended further.
+        if let Some(routes) = next.remove(&to) {
+            found.extend(routes);
+        }
+
+        if next.is_empty() {
+            break;
+        }
+
+        frontier = next;
+    }
+
+    // Stable sort keeps shorter routes first among the ones with the same
+    // output.
+    found.sort_by(|a, b| b.amount_out.cmp(&a.amount_out));
+    found.truncate(count);
+
+    found
+}
+
+/// Find up to `count` best routes from `from` to `to` which pass through all
+/// the `via` tokens in the given order, with at most `max_hops` swaps in
+/// total.
+///
+/// The route is searched leg by leg between the consecutive tokens, trying
+/// each split of the hops between the legs. Only the best routes of each leg
+/// are continued, so a route with a worse first leg which leaves better
+/// tokens for the next one could be missed.
+pub(crate) fn find_top_routes_via(
+    graph: &PairsGraph,
+    from: i32,
+    via: &[i32],
+    to: i32,
+    amount: U256,
+    count: usize,
+    max_hops: usize,
+) -> Vec<Route> {
+    let mut routes =
+        extend_top_routes_via(graph, &Route::new(from, amount), via, to, count, max_hops);
+
+    routes.sort_by(|a, b| b.amount_out.cmp(&a.amount_out));
+    routes.truncate(count);
+
+    routes
+}
+
+fn extend_top_routes_via(
+    graph: &PairsGraph,
+    start: &Route,
+    via: &[i32],
+    to: i32,
+    count: usize,
+    max_hops: usize,
+) -> Vec<Route> {
+    let Some((next, rest)) = via.split_first() else {
+        return extend_top_routes(graph, start, to, count, max_hops);
+    };
+
+    let mut routes: Vec<Route> = Vec::new();
+
+    // Each of the remaining legs takes at least one hop.
+    for leg_hops in 1..=max_hops.saturating_sub(rest.len() + 1) {
+        for leg in extend_top_routes(graph, start, *next, count, leg_hops) {
+            // Legs shorter than the limit were found with the smaller ones
+            // already.
+            let hops = leg.pairs.len() - start.pairs.len();
+            if hops < leg_hops {
+                continue;
+            }
+
+            routes.extend(extend_top_routes_via(
+                graph,
+                &leg,
+                rest,
+                to,
+                count,
+                max_hops - hops,
+            ));
+        }
+    }
+
+    routes
+}
+
+/// Split `amount` between several routes from `from` to `to`, so that the
+/// total output is bigger than the one of the best single route for large
+/// amounts, which suffer from the price impact.
+///
+/// The amount is divided into `parts` equal parts, and each is sent along
+/// the best route given the reserves changed by the previous ones, which
+/// approximately equalizes the marginal output of the routes. Parts sent
+/// along the same pairs are merged into a single route, and the routes are
+/// returned from the biggest input to the smallest one. Amounts smaller than
+/// `parts` are split into parts of a single unit.
+fn find_split_routes(
+    graph: &PairsGraph,
+    from: i32,
+    to: i32,
+    amount: U256,
+    parts: u32,
+    max_hops: usize,
+) -> Option<Vec<Route>> {
+    let parts = U256::from(parts.max(1)).min(amount.max(U256::one()));
+    let part = amount / parts;
+
+    let mut graph = graph.clone();
+    let mut routes: Vec<Route> = Vec::new();
+
+    let mut left = amount;
+    while !left.is_zero() {
+        // The remainder of the division goes with the last part.
+        let amount_in = if left < part * 2 { left } else { part };
+        left -= amount_in;
+
+        let route = find_best_route(&graph, from, to, amount_in, max_hops)?;
+        apply_route(&mut graph, &route);
+
+        match routes.iter_mut().find(|known| known.has_same_pairs(&route)) {
+            Some(known) => {
+                known.amount_in += route.amount_in;
+                known.amount_out += route.amount_out;
+            }
+            None => routes.push(route),
+        }
+    }
+
+    routes.sort_by(|a, b| b.amount_in.cmp(&a.amount_in));
+
+    (!routes.is_empty()).then_some(routes)
+}
+
+/// Update reserves of the pairs in the graph after the swaps of the route.
+fn apply_route(graph: &mut PairsGraph, route: &Route) {
+    let mut amount_in = route.amount_in;
+
+    for (hop, route_pair) in route.pairs.iter().enumerate() {
+        let token_in = route.tokens[hop];
+        let token_out = route.tokens[hop + 1];
+
+        let pair = graph
+            .edge_weight_mut(token_in, token_out)
+            .and_then(|pairs| {
+                pairs
+                    .iter_mut()
+                    .find(|pair| pair.address == route_pair.address)
+            })
+            .expect("route goes through the pairs of the graph");
+
+        let (reserve_in, reserve_out) = pair.reserves_from(token_in);
+        let amount_out = math::get_amount_out(amount_in, reserve_in, reserve_out, pair.fee)
+            .expect("route swaps are valid");
+
+        pair.apply_swap(token_in, amount_in, amount_out);
+        amount_in = amount_out;
+    }
+}
+
+/// Find the route from `from` to `to` which requires the smallest input to
+/// receive exactly `amount` of `to`.
+///
+/// Mirrors [`find_best_route`], but goes backwards from the goal token: each
+/// hop computes the input required for the amount already needed by the rest
+/// of the route, keeping only the cheapest route for each reached token.
+pub(crate) fn find_best_route_exact_out(
+    graph: &PairsGraph,
+    from: i32,
+    to: i32,
+    amount: U256,
+    max_hops: usize,
+) -> Option<Route> {
+    let mut frontier = HashMap::from([(to, Route::new(to, amount))]);
+    let mut best: Option<Route> = None;
+
+    for _ in 0..max_hops {
+        let mut next: HashMap<i32, Route> = HashMap::new();
+
+        for route in frontier.values() {
+            let token_out = route.first_token();
+
+            for (_, token_in, pairs) in graph.edges(token_out) {
+                if route.tokens.contains(&token_in) {
+                    continue;
+                }
+
+                for pair in pairs {
+                    let (reserve_in, reserve_out) = pair.reserves_from(token_in);
+                    let Some(amount_in) =
+                        math::get_amount_in(route.amount_in, reserve_in, reserve_out, pair.fee)
+                    else {
+                        continue;
+                    };
+
+                    let is_better = next
+                        .get(&token_in)
+                        .map_or(true, |known| amount_in < known.amount_in);
+
+                    if is_better {
+                        next.insert(token_in, route.prepend(token_in, pair, amount_in));
+                    }
+                }
+            }
+        }
+
+        // Routes that reached the start are not extended further.
+        if let Some(route) = next.remove(&from) {
+            if best
+                .as_ref()
+                .map_or(true, |best| route.amount_in < best.amount_in)
+            {
+                best = Some(route);
+            }
+        }
+
+        if next.is_empty() {
+            break;
+        }
+
+        frontier = next;
+    }
+
+    best
+}
+
+pub(crate) async fn get_token(
     conn: &mut PgConnection,
     address: ethers::types::Address,
 ) -> eyre::Result<TokenEntry> {
@@ -94,10 +897,7 @@ async fn get_token(
     Ok(token)
 }
 
-async fn get_token_by_id(
-    conn: &mut PgConnection,
-    id: i32,
-) -> eyre::Result<TokenEntry> {
+async fn get_token_by_id(conn: &mut PgConnection, id: i32) -> eyre::Result<TokenEntry> {
     let token = DB::token_by_id(&mut *conn, id)
         .await?
         .ok_or_else(|| eyre::eyre!("Token not found with id: {}", id))?;
@@ -105,20 +905,194 @@ async fn get_token_by_id(
     Ok(token)
 }
 
-/// Calculate the amount of tokens to swap on Uniswap
-///
-/// Formula:
-///
-/// $$
-/// \text{amountOut} = \frac{\text{amountIn} \times \text{reserveOut} \times 997}{\text{reserveIn} \times 1000 + \text{amountIn} \times 997}
-/// $$
-fn calculate_uniswap_swap_amount(
-    from_amount: BigDecimal,
-    from_reserve: BigDecimal,
-    to_reserve: BigDecimal,
-) -> BigDecimal {
-    let numerator = from_amount * to_reserve * BigDecimal::from(997);
-    let denominator = from_reserve * BigDecimal::from(1000) + from_amount * BigDecimal::from(997);
-
-    numerator / denominator
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::testing;
+
+    use super::*;
+
+    const DEEP: u128 = 1_000_000_000;
+
+    fn amount(value: u64) -> U256 {
+        U256::from(value)
+    }
+
+    #[test]
+    fn test_multi_hop_beats_direct() {
+        // The direct pair is shallow, the one through token 2 is deep.
+        let graph = testing::graph(&[
+            (1, 1, 3, 10_000, 10_000),
+            (2, 1, 2, DEEP, DEEP),
+            (3, 2, 3, DEEP, DEEP),
+        ]);
+
+        let route = find_best_route(&graph, 1, 3, amount(1000), MAX_HOPS).expect("route exists");
+        assert_eq!(route.tokens, vec![1, 2, 3]);
+        assert_eq!(route.quote(route.amount_in), Some(route.amount_out));
+
+        // Small amounts are better swapped directly, saving the second fee.
+        let route = find_best_route(&graph, 1, 3, amount(10), MAX_HOPS).expect("route exists");
+        assert_eq!(route.tokens, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_no_revisit() {
+        // Swapping 2 -> 3 -> 2 through the two mispriced pairs is
+        // profitable, but routes can't visit token 2 twice.
+        let graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 2, 3, DEEP, 2 * DEEP),
+            (3, 2, 3, 2 * DEEP, DEEP),
+            (4, 2, 4, DEEP, DEEP),
+        ]);
+
+        let routes = find_top_routes(&graph, 1, 4, amount(1000), 5, MAX_HOPS);
+        assert_eq!(routes[0].tokens, vec![1, 2, 4]);
+
+        for route in &routes {
+            let unique: HashSet<i32> = route.tokens.iter().copied().collect();
+            assert_eq!(
+                unique.len(),
+                route.tokens.len(),
+                "route {:?} revisits a token",
+                route.tokens
+            );
+        }
+    }
+
+    #[test]
+    fn test_top_routes() {
+        let graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 1, 2, DEEP / 2, DEEP / 2),
+            (3, 1, 3, DEEP, DEEP),
+            (4, 3, 2, DEEP, DEEP),
+        ]);
+
+        let routes = find_top_routes(&graph, 1, 2, amount(1_000_000), 5, MAX_HOPS);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[0].pairs[0].id, 1);
+
+        assert!(routes
+            .windows(2)
+            .all(|pair| pair[0].amount_out >= pair[1].amount_out));
+        for (i, route) in routes.iter().enumerate() {
+            assert!(routes[i + 1..]
+                .iter()
+                .all(|other| !route.has_same_pairs(other)));
+        }
+
+        let best =
+            find_best_route(&graph, 1, 2, amount(1_000_000), MAX_HOPS).expect("route exists");
+        assert!(best.has_same_pairs(&routes[0]));
+    }
+
+    #[test]
+    fn test_routes_via() {
+        let graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 1, 3, DEEP, DEEP),
+            (3, 3, 4, DEEP, DEEP),
+            (4, 4, 2, DEEP, DEEP),
+            (5, 3, 2, DEEP, DEEP),
+            (6, 1, 4, DEEP, DEEP),
+        ]);
+
+        let routes = find_top_routes_via(&graph, 1, &[3, 4], 2, amount(1000), 3, MAX_HOPS);
+        assert_eq!(routes[0].tokens, vec![1, 3, 4, 2]);
+
+        let routes = find_top_routes_via(&graph, 1, &[4, 3], 2, amount(1000), 3, MAX_HOPS);
+        assert_eq!(routes[0].tokens, vec![1, 4, 3, 2]);
+
+        // Three legs don't fit into two hops.
+        assert!(find_top_routes_via(&graph, 1, &[3, 4], 2, amount(1000), 3, 2).is_empty());
+    }
+
+    #[test]
+    fn test_max_hops() {
+        let graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 2, 3, DEEP, DEEP),
+            (3, 3, 4, DEEP, DEEP),
+        ]);
+
+        assert!(find_best_route(&graph, 1, 4, amount(1000), 2).is_none());
+        assert_eq!(
+            find_best_route(&graph, 1, 4, amount(1000), 3).map(|route| route.tokens),
+            Some(vec![1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn test_exact_out() {
+        let graph = testing::graph(&[
+            (1, 1, 3, 10_000, 10_000),
+            (2, 1, 2, DEEP, DEEP),
+            (3, 2, 3, DEEP, DEEP),
+        ]);
+
+        let route =
+            find_best_route_exact_out(&graph, 1, 3, amount(1000), MAX_HOPS).expect("route exists");
+        assert_eq!(route.tokens, vec![1, 2, 3]);
+        assert!(route.quote(route.amount_in).expect("route swaps are valid") >= amount(1000));
+    }
+
+    #[test]
+    fn test_split_routes() {
+        let graph = testing::graph(&[
+            (1, 1, 2, 1_000_000, 1_000_000),
+            (2, 1, 2, 1_000_000, 1_000_000),
+        ]);
+
+        let routes =
+            find_split_routes(&graph, 1, 2, amount(200_000), 4, MAX_HOPS).expect("routes exist");
+        assert_eq!(routes.len(), 2);
+        assert_eq!(
+            routes
+                .iter()
+                .map(|route| route.amount_in)
+                .fold(U256::zero(), |a, b| a + b),
+            amount(200_000)
+        );
+
+        let single =
+            find_best_route(&graph, 1, 2, amount(200_000), MAX_HOPS).expect("route exists");
+        let total_out = routes
+            .iter()
+            .map(|route| route.amount_out)
+            .fold(U256::zero(), |a, b| a + b);
+        assert!(total_out > single.amount_out);
+    }
+
+    #[test]
+    fn test_split_amount_smaller_than_parts() {
+        let graph = testing::graph(&[(1, 1, 2, DEEP, DEEP)]);
+
+        let routes =
+            find_split_routes(&graph, 1, 2, amount(3), 10, MAX_HOPS).expect("routes exist");
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].amount_in, amount(3));
+
+        assert!(find_split_routes(&graph, 1, 2, U256::zero(), 10, MAX_HOPS).is_none());
+    }
+
+    #[test]
+    fn test_remove_pair() {
+        let mut graph = testing::graph(&[
+            (1, 1, 2, DEEP, DEEP),
+            (2, 1, 2, DEEP, DEEP),
+            (3, 2, 3, DEEP, DEEP),
+        ]);
+
+        remove_pair(&mut graph, 1, 2, 1);
+        assert_eq!(graph.edge_weight(1, 2).map(Vec::len), Some(1));
+
+        // Token order of the stored pair doesn't matter in the undirected
+        // graph.
+        remove_pair(&mut graph, 2, 1, 2);
+        assert!(!graph.contains_edge(1, 2));
+        assert!(find_best_route(&graph, 1, 3, amount(1000), MAX_HOPS).is_none());
+    }
 }
@@ -1,2 +1,10 @@
-mod find;
+mod arbitrage;
+pub(crate) mod export;
+pub(crate) mod find;
+mod prices;
+mod serve;
+pub use arbitrage::arbitrage;
+pub use export::export;
 pub use find::find;
+pub use prices::prices;
+pub use serve::serve;
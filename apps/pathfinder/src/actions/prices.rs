@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+use color_eyre::eyre;
+use config::Config;
+use database::{prices::NewTokenPrice, DB};
+use sqlx::types::BigDecimal;
+
+use super::find::get_token;
+use crate::{cli::PricesArgs, filters::GraphFilter, graph::PairGraph, pricing};
+
+/// Compute prices of all the tokens from the latest reserves and store them
+/// at the latest indexed block.
+pub async fn prices(config: Config, args: PricesArgs) -> eyre::Result<()> {
+    let base_token = args
+        .base_token
+        .or(config.pathfinder.base_token)
+        .ok_or_else(|| {
+            eyre::eyre!("Base token is set neither in the arguments nor in the config")
+        })?;
+
+    let database = DB::from_url(&config.database.url).await?;
+
+    let block = database
+        .latest_blocks(1)
+        .await?
+        .pop()
+        .ok_or_else(|| eyre::eyre!("No blocks are indexed yet"))?;
+
+    let mut txn = database.pool().begin().await?;
+
+    let filter = GraphFilter::load(&mut txn, &config.pathfinder.filters, Some(base_token)).await?;
+    let pair_graph = PairGraph::load(&mut txn, filter, None).await?;
+    let graph = pair_graph.graph();
+
+    let base_token = get_token(&mut txn, base_token).await?;
+
+    let decimals = pricing::known_decimals(&DB::tokens(&mut txn).await?);
+
+    let mut stablecoins = Vec::with_capacity(config.pathfinder.stablecoins.len());
+    for address in &config.pathfinder.stablecoins {
+        match DB::token_by_address(&mut txn, *address).await? {
+            Some(token) => stablecoins.push(token.id),
+            None => eyre::bail!("Stablecoin {:?} isn't indexed", address),
+        }
+    }
+
+    let prices = pricing::price_tokens(graph, &decimals, base_token.id, &stablecoins);
+
+    let new_prices: Vec<NewTokenPrice> = prices
+        .into_iter()
+        .filter_map(|(token, price)| {
+            Some(NewTokenPrice {
+                token,
+                price_base: to_decimal(price.price_base)?,
+                price_usd: price.price_usd.and_then(to_decimal),
+                liquidity: to_decimal(price.liquidity),
+            })
+        })
+        .collect();
+
+    DB::insert_token_prices(&mut txn, block.id, base_token.id, &new_prices).await?;
+
+    txn.commit().await?;
+
+    let usd_count = new_prices
+        .iter()
+        .filter(|price| price.price_usd.is_some())
+        .count();
+    println!(
+        "Stored prices of {} tokens in {} at block {}, {} of them in USD",
+        new_prices.len(),
+        base_token.symbol,
+        block.height,
+        usd_count,
+    );
+
+    Ok(())
+}
+
+/// Convert the price to the decimal, `None` for infinite values.
+fn to_decimal(value: f64) -> Option<BigDecimal> {
+    if !value.is_finite() {
+        return None;
+    }
+
+    BigDecimal::from_str(&value.to_string()).ok()
+}
@@ -0,0 +1,128 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::eyre;
+use config::Config;
+use database::{reserves::ReservesNotification, DB};
+use sqlx::PgConnection;
+use tokio::{net::TcpListener, sync::RwLock};
+
+use crate::{
+    actions::find::factory_names,
+    cli::ServeArgs,
+    server::{self, GraphState, ReloadedPair, SharedState},
+};
+
+/// Delay before listening to the reserves again after an error.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Serve the JSON HTTP API, keeping the graph up to date with the reserves
+/// notifications of the database.
+pub async fn serve(config: Config, args: ServeArgs) -> eyre::Result<()> {
+    let database = DB::from_url(&config.database.url).await?;
+
+    let state = Arc::new(RwLock::new(
+        GraphState::load(
+            &database,
+            &config.pathfinder.filters,
+            config.pathfinder.base_token,
+        )
+        .await?,
+    ));
+
+    tokio::spawn(follow_reserves(database, state.clone()));
+
+    let listener = TcpListener::bind(args.listen).await?;
+
+    tracing::info!("Listening on {}", args.listen);
+
+    axum::serve(listener, server::router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Apply changed reserves to the graph, listening again after errors.
+async fn follow_reserves(database: DB, state: SharedState) {
+    loop {
+        if let Err(err) = listen_reserves(&database, &state).await {
+            tracing::warn!("Failed to follow reserves: {:?}", err);
+        }
+
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+/// Apply the notifications to the graph. Data is read from the database with
+/// no lock held, and the write lock is taken only to apply it, so quotes
+/// aren't blocked by the database round-trips.
+async fn listen_reserves(database: &DB, state: &SharedState) -> eyre::Result<()> {
+    let mut listener = database.reserves_listener().await?;
+
+    loop {
+        // Notifications sent while the connection was down are lost, so the
+        // reserves stored meanwhile are read from the table.
+        let mut conn = database.pool().acquire().await?;
+
+        let from_height = state.read().await.graph.catch_up_height();
+        let updates = DB::reserves_since(&mut conn, from_height).await?;
+        let new_pairs = state.write().await.graph.catch_up(&updates);
+        for pair_id in new_pairs {
+            reload_pair(&mut conn, state, pair_id).await?;
+        }
+
+        tracing::info!(
+            from_height,
+            "Caught up with {} reserves entries",
+            updates.len()
+        );
+
+        while let Some(notification) = listener.try_recv().await? {
+            let notification: ReservesNotification = notification.payload().parse()?;
+
+            let reloaded = state.write().await.graph.apply_notification(&notification);
+            if let Some(pair_id) = reloaded {
+                reload_pair(&mut conn, state, pair_id).await?;
+            }
+        }
+
+        tracing::warn!("Connection to the database was lost, reconnecting");
+    }
+}
+
+/// Load the pair with its latest reserves again, with the tokens and the
+/// factory it brings if they are new to the state.
+async fn reload_pair(
+    conn: &mut PgConnection,
+    state: &SharedState,
+    pair_id: i32,
+) -> eyre::Result<()> {
+    let pair = DB::pair_with_reserves(&mut *conn, pair_id).await?;
+
+    let (missing_tokens, is_factory_missing) = match &pair {
+        Some(pair) => state.read().await.missing_metadata(pair),
+        None => (Vec::new(), false),
+    };
+
+    let mut tokens = Vec::with_capacity(missing_tokens.len());
+    for token_id in missing_tokens {
+        tokens.extend(DB::token_by_id(&mut *conn, token_id).await?);
+    }
+
+    let factory_names = if is_factory_missing {
+        Some(factory_names(&mut *conn).await?)
+    } else {
+        None
+    };
+
+    state.write().await.apply_reloaded_pair(ReloadedPair {
+        pair_id,
+        pair,
+        tokens,
+        factory_names,
+    });
+
+    Ok(())
+}
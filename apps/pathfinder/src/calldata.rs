@@ -0,0 +1,157 @@
+//! Transactions of the `UniswapV2Router02` swaps along the found routes.
+
+use bindings::i_uniswap_v2_router_02::{
+    SwapExactTokensForTokensCall, SwapTokensForExactTokensCall,
+};
+use ethers::{
+    abi::AbiEncode,
+    types::{Address, Bytes, U256},
+};
+use serde::{Serialize, Serializer};
+
+/// Amounts of the router swap, serialized as decimal strings, as they don't
+/// fit into JSON numbers.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(untagged)]
+pub enum SwapAmounts {
+    /// Swap the exact input for at least the minimum output.
+    ExactInput {
+        #[serde(serialize_with = "serialize_decimal")]
+        amount_in: U256,
+        #[serde(serialize_with = "serialize_decimal")]
+        amount_out_min: U256,
+    },
+
+    /// Swap at most the maximum input for the exact output.
+    ExactOutput {
+        #[serde(serialize_with = "serialize_decimal")]
+        amount_out: U256,
+        #[serde(serialize_with = "serialize_decimal")]
+        amount_in_max: U256,
+    },
+}
+
+fn serialize_decimal<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Router transaction ready to be signed, with the parameters of the call.
+#[derive(Debug, Serialize)]
+pub struct SwapTransaction {
+    /// Address of the router.
+    pub to: Address,
+
+    /// ABI encoded call of the router.
+    pub data: Bytes,
+
+    /// Wei sent with the transaction, always zero for the token swaps.
+    pub value: String,
+
+    /// Name of the called function.
+    pub function: &'static str,
+
+    /// Addresses of the tokens on the route.
+    pub path: Vec<Address>,
+
+    /// Receiver of the output tokens.
+    pub recipient: Address,
+
+    /// Unix timestamp after which the router reverts the swap.
+    pub deadline: u64,
+
+    #[serde(flatten)]
+    pub amounts: SwapAmounts,
+}
+
+/// Build the transaction calling `swapExactTokensForTokens` or
+/// `swapTokensForExactTokens` of the router for the amounts.
+pub fn swap_transaction(
+    router: Address,
+    amounts: SwapAmounts,
+    path: Vec<Address>,
+    recipient: Address,
+    deadline: u64,
+) -> SwapTransaction {
+    let (function, data) = match amounts {
+        SwapAmounts::ExactInput {
+            amount_in,
+            amount_out_min,
+        } => {
+            let call = SwapExactTokensForTokensCall {
+                amount_in,
+                amount_out_min,
+                path: path.clone(),
+                to: recipient,
+                deadline: deadline.into(),
+            };
+
+            ("swapExactTokensForTokens", call.encode())
+        }
+        SwapAmounts::ExactOutput {
+            amount_out,
+            amount_in_max,
+        } => {
+            let call = SwapTokensForExactTokensCall {
+                amount_out,
+                amount_in_max,
+                path: path.clone(),
+                to: recipient,
+                deadline: deadline.into(),
+            };
+
+            ("swapTokensForExactTokens", call.encode())
+        }
+    };
+
+    SwapTransaction {
+        to: router,
+        data: data.into(),
+        value: "0".to_string(),
+        function,
+        path,
+        recipient,
+        deadline,
+        amounts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_transaction() {
+        let path = vec![Address::repeat_byte(1), Address::repeat_byte(2)];
+        let recipient = Address::repeat_byte(3);
+
+        let amounts = SwapAmounts::ExactInput {
+            amount_in: 1000.into(),
+            amount_out_min: 990.into(),
+        };
+        let transaction = swap_transaction(
+            Address::zero(),
+            amounts,
+            path.clone(),
+            recipient,
+            1_700_000_000,
+        );
+
+        assert_eq!(transaction.data[..4], [0x38, 0xed, 0x17, 0x39]);
+        // Selector, 5 head words, path length and 2 addresses.
+        assert_eq!(transaction.data.len(), 4 + 32 * 8);
+        assert_eq!(transaction.data[4..36], U256::from(1000).encode()[..]);
+
+        let amounts = SwapAmounts::ExactOutput {
+            amount_out: 990.into(),
+            amount_in_max: 1000.into(),
+        };
+        let transaction =
+            swap_transaction(Address::zero(), amounts, path, recipient, 1_700_000_000);
+
+        assert_eq!(transaction.data[..4], [0x88, 0x03, 0xdb, 0xee]);
+
+        let json = serde_json::to_value(&transaction).expect("transaction is serializable");
+        assert_eq!(json["amount_out"], "990");
+        assert_eq!(json["amount_in_max"], "1000");
+    }
+}
@@ -1,11 +1,12 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf, str::FromStr};
 
-use config::Config;
 use clap::{Args, Parser, Subcommand};
-use color_eyre::eyre;
-use ethers::types::Address;
+use color_eyre::eyre::{self, Context};
+use config::Config;
+use ethers::types::{Address, U256};
+use tracing::level_filters::LevelFilter;
 
-use crate::actions;
+use crate::actions::{self, export::ExportFormat, find::MAX_HOPS};
 
 #[derive(Debug, Parser)]
 pub struct Cli {
@@ -13,7 +14,7 @@ pub struct Cli {
     pub command: Commands,
 
     #[clap(short = 'v', action = clap::ArgAction::Count)]
-    pub verbosity: u32,
+    pub verbosity: u8,
 
     #[clap(short = 'c', long)]
     pub config: PathBuf,
@@ -23,8 +24,33 @@ impl Cli {
     pub async fn run(self) -> eyre::Result<()> {
         let config = Config::load(&self.config)?;
 
+        let level_filter = LevelFilter::from_str(config.logger.level.as_str())
+            .wrap_err("Invalid logger level specified")?;
+
+        let log_path = config.logger.path.clone();
+        tracing_subscriber::fmt()
+            .with_writer(move || -> Box<dyn std::io::Write> {
+                if let Some(log_file) = &log_path {
+                    let file = std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(log_file)
+                        .expect("Failed to open log file");
+
+                    Box::new(file)
+                } else {
+                    Box::new(std::io::stderr())
+                }
+            })
+            .with_max_level(level_filter)
+            .init();
+
         match self.command {
             Commands::Find(args) => actions::find(config, args).await?,
+            Commands::Arbitrage(args) => actions::arbitrage(config, args).await?,
+            Commands::Prices(args) => actions::prices(config, args).await?,
+            Commands::Serve(args) => actions::serve(config, args).await?,
+            Commands::Export(args) => actions::export(config, args).await?,
         }
 
         Ok(())
@@ -34,6 +60,18 @@ impl Cli {
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     Find(FindArgs),
+
+    /// Find profitable cycles of swaps and their optimal input amounts.
+    Arbitrage(ArbitrageArgs),
+
+    /// Compute prices of the tokens and store them at the latest block.
+    Prices(PricesArgs),
+
+    /// Serve quotes over the JSON HTTP API.
+    Serve(ServeArgs),
+
+    /// Export the pairs graph to render or analyze it with other tools.
+    Export(ExportArgs),
 }
 
 #[derive(Debug, Args)]
@@ -44,6 +82,149 @@ pub struct FindArgs {
     #[clap(short, long)]
     pub to: Address,
 
+    /// Amount of the input token in its smallest units.
+    #[clap(short, long, value_parser = parse_amount)]
+    pub value: U256,
+
+    /// Treat `--value` as the exact amount of the `--to` token to receive and
+    /// find the smallest amount of the `--from` token required for it.
+    #[clap(long)]
+    pub exact_out: bool,
+
+    /// Split the input into the given number of equal parts, each sent along
+    /// the best route given the price impact of the previous ones.
+    #[clap(long, conflicts_with = "exact_out", value_parser = clap::value_parser!(u32).range(1..))]
+    pub split: Option<u32>,
+
+    /// Find the given number of the best distinct routes, e.g. to fall back
+    /// to if the pairs of the best one change before the swap is included.
+    #[clap(long, conflicts_with_all = ["exact_out", "split"], value_parser = clap::value_parser!(u32).range(1..))]
+    pub top: Option<u32>,
+
+    /// Slippage tolerance in percents to compute the minimum output, or the
+    /// maximum input for `--exact-out`. Stored in basis points.
+    #[clap(long = "slippage", default_value = "0.5", value_parser = parse_slippage)]
+    pub slippage_bps: u32,
+
+    /// Maximum number of swaps in the route.
+    #[clap(long, default_value_t = MAX_HOPS as u32, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_hops: u32,
+
+    /// Tokens the route must pass through, in the given order.
+    #[clap(long, conflicts_with_all = ["exact_out", "split"])]
+    pub via: Vec<Address>,
+
+    /// Tokens or pairs the route must not use.
+    #[clap(long)]
+    pub avoid: Vec<Address>,
+
+    /// Print the router transaction of the route as JSON instead, which
+    /// requires all the pairs of the route to belong to a factory with the
+    /// router in the config.
+    #[clap(long, requires = "recipient", conflicts_with_all = ["split", "top"])]
+    pub calldata: bool,
+
+    /// Receiver of the output tokens of the swap.
+    #[clap(long, requires = "calldata")]
+    pub recipient: Option<Address>,
+
+    /// Seconds from now after which the router reverts the swap.
+    #[clap(long, default_value = "1200")]
+    pub deadline: u64,
+
+    /// Route against the reserves at the block height, e.g. stored by the
+    /// `snapshot` of the bootstrapper, instead of the latest ones. Pairs
+    /// without reserves stored at the height use the latest ones before it.
+    #[clap(long)]
+    pub at_block: Option<u64>,
+
+    /// Token to measure liquidity in for `pathfinder.filters.min_liquidity`,
+    /// `pathfinder.base_token` of the config by default.
+    #[clap(short, long)]
+    pub base_token: Option<Address>,
+}
+
+#[derive(Debug, Args)]
+pub struct ArbitrageArgs {
+    /// Token to start cycles from, `pathfinder.base_token` of the config by
+    /// default.
+    #[clap(short, long)]
+    pub base_token: Option<Address>,
+
+    /// Maximum number of Bellman-Ford rounds, cycles further from the base
+    /// token could be missed.
+    #[clap(short, long, default_value = "16")]
+    pub rounds: usize,
+
+    /// Maximum number of the most profitable cycles to report.
+    #[clap(short, long, default_value = "10")]
+    pub limit: usize,
+
+    /// Search the cycles in the reserves at the block height instead of the
+    /// latest ones. Pairs without reserves stored at the height use the
+    /// latest ones before it.
+    #[clap(long)]
+    pub at_block: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct PricesArgs {
+    /// Token to price the tokens in, `pathfinder.base_token` of the config by
+    /// default.
+    #[clap(short, long)]
+    pub base_token: Option<Address>,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Address to listen on.
+    #[clap(short, long, default_value = "127.0.0.1:8080")]
+    pub listen: SocketAddr,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Format of the exported graph.
+    #[clap(short, long, value_enum, default_value = "dot")]
+    pub format: ExportFormat,
+
+    /// File to write the graph to, stdout by default.
     #[clap(short, long)]
-    pub value: u64,
+    pub output: Option<PathBuf>,
+
+    /// Export only the pairs with the value of both reserves at least the
+    /// given amount of whole base tokens.
+    #[clap(short, long)]
+    pub min_liquidity: Option<f64>,
+
+    /// Token to measure liquidity in, `pathfinder.base_token` of the config
+    /// by default.
+    #[clap(short, long)]
+    pub base_token: Option<Address>,
+
+    /// Export only the tokens reachable from the given one in `--hops` swaps.
+    #[clap(short, long)]
+    pub around: Option<Address>,
+
+    /// Number of swaps from the `--around` token.
+    #[clap(long, default_value = "1", requires = "around")]
+    pub hops: usize,
+}
+
+/// Parse a decimal amount, as `U256::from_str` parses hex.
+fn parse_amount(value: &str) -> Result<U256, String> {
+    U256::from_dec_str(value).map_err(|err| err.to_string())
+}
+
+/// Parse the percents into basis points.
+fn parse_slippage(value: &str) -> Result<u32, String> {
+    let percents: f64 = value
+        .parse()
+        .map_err(|err: std::num::ParseFloatError| err.to_string())?;
+
+    if !(0.0..=100.0).contains(&percents) {
+        return Err("slippage must be from 0 to 100 percents".to_string());
+    }
+
+    Ok((percents * 100.0).round() as u32)
 }
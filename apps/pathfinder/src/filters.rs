@@ -0,0 +1,241 @@
+//! Filters of the pairs applied when the graph is built, so dust pools and
+//! scam tokens don't pollute the routes.
+
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre;
+use config::GraphFilters;
+use database::{format_address, DB};
+use ethers::types::{Address, U256};
+use sqlx::PgConnection;
+
+use crate::{
+    actions::find::{PairEdge, PairsGraph},
+    math,
+    pricing::{self, TokenPrice},
+};
+
+/// Minimal liquidity of the pairs with what's needed to measure it.
+struct MinLiquidity {
+    /// Minimal value of both reserves in whole base tokens.
+    value: f64,
+
+    base_token: i32,
+    decimals: HashMap<i32, u32>,
+}
+
+/// [`GraphFilters`] of the config resolved to the token ids.
+#[derive(Default)]
+pub struct GraphFilter {
+    /// Tokens which pairs are used, all the tokens if `None`.
+    allowed: Option<HashSet<i32>>,
+
+    /// Tokens which pairs aren't used.
+    denied: HashSet<i32>,
+
+    min_liquidity: Option<MinLiquidity>,
+}
+
+impl GraphFilter {
+    /// Resolve the filters, `base_token` is required only to filter by
+    /// liquidity. Listed tokens which aren't indexed are ignored, as they
+    /// have no pairs anyway.
+    pub async fn load(
+        conn: &mut PgConnection,
+        filters: &GraphFilters,
+        base_token: Option<Address>,
+    ) -> eyre::Result<Self> {
+        let tokens = DB::tokens(conn).await?;
+
+        let token_ids: HashMap<&str, i32> = tokens
+            .iter()
+            .map(|token| (token.address.trim_end(), token.id))
+            .collect();
+        let resolve = |addresses: &[Address]| -> HashSet<i32> {
+            addresses
+                .iter()
+                .filter_map(|address| token_ids.get(format_address(*address).as_str()).copied())
+                .collect()
+        };
+
+        let allowed = (!filters.allow_tokens.is_empty()).then(|| resolve(&filters.allow_tokens));
+
+        let mut denied = resolve(&filters.deny_tokens);
+        if filters.exclude_unknown {
+            denied.extend(
+                tokens
+                    .iter()
+                    .filter(|token| token.has_unknown_metadata())
+                    .map(|token| token.id),
+            );
+        }
+
+        // Routes through these tokens return less than quoted.
+        denied.extend(
+            tokens
+                .iter()
+                .filter(|token| {
+                    token
+                        .transfer_tax_bps
+                        .map_or(false, |tax| tax as u32 > filters.max_transfer_tax_bps)
+                        || (!filters.allow_rebasing && token.rebasing == Some(true))
+                })
+                .map(|token| token.id),
+        );
+
+        let min_liquidity = match filters.min_liquidity {
+            Some(value) => {
+                let base_token = base_token.ok_or_else(|| {
+                    eyre::eyre!("Base token is required to filter pairs by liquidity")
+                })?;
+                let base_token = *token_ids
+                    .get(format_address(base_token).as_str())
+                    .ok_or_else(|| eyre::eyre!("Base token {:?} isn't indexed", base_token))?;
+
+                let decimals = pricing::known_decimals(&tokens);
+
+                Some(MinLiquidity {
+                    value,
+                    base_token,
+                    decimals,
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            allowed,
+            denied,
+            min_liquidity,
+        })
+    }
+
+    /// Check if the pair of the tokens passes the token filters.
+    pub fn allows_pair(&self, token0: i32, token1: i32) -> bool {
+        [token0, token1].iter().all(|token| {
+            !self.denied.contains(token)
+                && self
+                    .allowed
+                    .as_ref()
+                    .map_or(true, |allowed| allowed.contains(token))
+        })
+    }
+
+    /// Return ids of the pairs of the graph with the liquidity below the
+    /// minimal one, including the pairs which tokens can't be priced.
+    pub fn illiquid_pairs(&self, graph: &PairsGraph) -> Vec<i32> {
+        let Some(min_liquidity) = &self.min_liquidity else {
+            return Vec::new();
+        };
+
+        let prices = pricing::price_tokens(
+            graph,
+            &min_liquidity.decimals,
+            min_liquidity.base_token,
+            &[],
+        );
+
+        graph
+            .all_edges()
+            .flat_map(|(token_a, token_b, pairs)| {
+                pairs.iter().map(move |pair| (token_a, token_b, pair))
+            })
+            .filter(|(token_a, token_b, pair)| {
+                pair_liquidity(pair, *token_a, *token_b, &prices, &min_liquidity.decimals)
+                    .map_or(true, |liquidity| liquidity < min_liquidity.value)
+            })
+            .map(|(_, _, pair)| pair.id)
+            .collect()
+    }
+}
+
+/// Return the value of both reserves of the pair in whole base tokens, or
+/// `None` if any of its tokens isn't priced.
+pub fn pair_liquidity(
+    pair: &PairEdge,
+    token_a: i32,
+    token_b: i32,
+    prices: &HashMap<i32, TokenPrice>,
+    decimals: &HashMap<i32, u32>,
+) -> Option<f64> {
+    let value = |token: i32, reserve: U256| -> Option<f64> {
+        let whole = math::to_f64(reserve) / 10f64.powi(*decimals.get(&token)? as i32);
+
+        Some(whole * prices.get(&token)?.price_base)
+    };
+
+    let (reserve_a, reserve_b) = pair.reserves_from(token_a);
+
+    Some(value(token_a, reserve_a)? + value(token_b, reserve_b)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing;
+
+    use super::*;
+
+    fn price(price_base: f64) -> TokenPrice {
+        TokenPrice {
+            price_base,
+            price_usd: None,
+            liquidity: f64::INFINITY,
+        }
+    }
+
+    #[test]
+    fn test_allows_pair() {
+        assert!(GraphFilter::default().allows_pair(1, 2));
+
+        let filter = GraphFilter {
+            allowed: Some(HashSet::from([1, 2, 3])),
+            denied: HashSet::from([2]),
+            min_liquidity: None,
+        };
+
+        assert!(filter.allows_pair(1, 3));
+        // Denied tokens are left out even if they are allowed.
+        assert!(!filter.allows_pair(1, 2));
+        assert!(!filter.allows_pair(2, 3));
+        assert!(!filter.allows_pair(1, 4));
+    }
+
+    #[test]
+    fn test_pair_liquidity() {
+        let pair = testing::pair(1, 1, 100, 500);
+        let prices = HashMap::from([(1, price(1.0)), (2, price(2.0))]);
+        let decimals = HashMap::from([(1, 0), (2, 2)]);
+
+        assert_eq!(pair_liquidity(&pair, 1, 2, &prices, &decimals), Some(110.0));
+        assert_eq!(pair_liquidity(&pair, 2, 1, &prices, &decimals), Some(110.0));
+
+        let prices = HashMap::from([(1, price(1.0))]);
+        assert_eq!(pair_liquidity(&pair, 1, 2, &prices, &decimals), None);
+    }
+
+    #[test]
+    fn test_illiquid_pairs() {
+        let graph = testing::graph(&[
+            (1, 1, 2, 1_000, 1_000),
+            (2, 1, 3, 10, 10),
+            (3, 1, 2, 50, 50),
+            // Not connected to the base token, so its tokens aren't priced.
+            (4, 4, 5, 1_000_000, 1_000_000),
+        ]);
+
+        let filter = GraphFilter {
+            min_liquidity: Some(MinLiquidity {
+                value: 100.0,
+                base_token: 1,
+                decimals: (1..=5).map(|token| (token, 0)).collect(),
+            }),
+            ..Default::default()
+        };
+
+        let mut illiquid = filter.illiquid_pairs(&graph);
+        illiquid.sort_unstable();
+
+        assert_eq!(illiquid, vec![2, 4]);
+        assert!(GraphFilter::default().illiquid_pairs(&graph).is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+//! Pairs graph kept in memory and updated with the changed reserves instead
+//! of being loaded again.
+
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::{self, Context};
+use database::{
+    reserves::{decimal_to_reserve, ReservesNotification, ReservesUpdate},
+    PairsStreamEntry, DB,
+};
+use ethers::{providers::StreamExt, types::U256};
+use petgraph::prelude::UnGraphMap;
+use sqlx::PgConnection;
+
+use crate::{
+    actions::find::{PairEdge, PairsGraph},
+    filters::GraphFilter,
+};
+
+/// Number of the blocks before the latest applied ones which reserves are
+/// read again when catching up.
+const CATCH_UP_DEPTH: i64 = 64;
+
+/// Where the pair is in the graph and how recent its reserves are.
+#[derive(Debug, Clone, Copy)]
+struct PairLocation {
+    token0: i32,
+    token1: i32,
+
+    /// Height of the block of the reserves in the graph.
+    block_height: i64,
+}
+
+/// Pairs graph which is loaded once and then updated with the reserves
+/// stored since, e.g. from the notifications of [`DB::reserves_listener`].
+pub struct PairGraph {
+    graph: PairsGraph,
+
+    /// Locations of the pairs by their ids.
+    pairs: HashMap<i32, PairLocation>,
+
+    /// Ids of the pairs by their addresses.
+    pair_ids: HashMap<String, i32>,
+
+    /// Height of the block of the latest reserves applied, to catch up from.
+    last_block_height: i64,
+
+    filter: GraphFilter,
+
+    /// Ids of the pairs left out by the filter, so their reserves aren't
+    /// loaded again.
+    excluded: HashSet<i32>,
+}
+
+impl PairGraph {
+    /// Load all the pairs passing the filter with their reserves at the block
+    /// height `at_block`, the latest ones by default. Liquidity of the pairs
+    /// is checked only here, so pairs which become liquid or illiquid later
+    /// stay as they were until the graph is loaded again.
+    ///
+    /// Reserves at `at_block` are the latest ones stored up to that height,
+    /// which are exact only if the reserves were followed past it or a
+    /// `snapshot` of it was taken, so a missing block is warned about.
+    pub async fn load(
+        conn: &mut PgConnection,
+        filter: GraphFilter,
+        at_block: Option<i64>,
+    ) -> eyre::Result<Self> {
+        if let Some(height) = at_block {
+            if !DB::has_block_at_height(&mut *conn, height).await? {
+                tracing::warn!(
+                    "Block {} isn't stored, reserves stored before it could be outdated",
+                    height
+                );
+            }
+        }
+
+        let mut pair_graph = Self::new(filter);
+
+        let mut pairs_stream = DB::pairs_stream(conn, at_block).await?;
+        while let Some(result) = pairs_stream.next().await {
+            let pair = result.wrap_err("Failed to get pair from database")?;
+
+            pair_graph.insert_pair(&pair);
+        }
+
+        pair_graph.exclude_illiquid();
+
+        Ok(pair_graph)
+    }
+
+    /// Build the graph of the given pairs the way [`Self::load`] does with the
+    /// stored ones.
+    #[cfg(test)]
+    pub fn from_pairs(pairs: &[PairsStreamEntry], filter: GraphFilter) -> Self {
+        let mut pair_graph = Self::new(filter);
+
+        for pair in pairs {
+            pair_graph.insert_pair(pair);
+        }
+
+        pair_graph.exclude_illiquid();
+
+        pair_graph
+    }
+
+    fn new(filter: GraphFilter) -> Self {
+        Self {
+            graph: UnGraphMap::new(),
+            pairs: HashMap::new(),
+            pair_ids: HashMap::new(),
+            last_block_height: 0,
+            filter,
+            excluded: HashSet::new(),
+        }
+    }
+
+    fn exclude_illiquid(&mut self) {
+        for id in self.filter.illiquid_pairs(&self.graph) {
+            self.remove_pair(id);
+            self.excluded.insert(id);
+        }
+    }
+
+    pub fn graph(&self) -> &PairsGraph {
+        &self.graph
+    }
+
+    /// Take the graph out, for the commands which change it without
+    /// following the reserves.
+    pub fn into_graph(self) -> PairsGraph {
+        self.graph
+    }
+
+    /// Return ids of the tokens of the pair and the pair itself by its
+    /// address.
+    pub fn pair_by_address(&self, address: &str) -> Option<(i32, i32, &PairEdge)> {
+        let id = *self.pair_ids.get(address)?;
+        let location = self.pairs.get(&id)?;
+
+        Some((location.token0, location.token1, self.edge(id, location)?))
+    }
+
+    fn edge(&self, id: i32, location: &PairLocation) -> Option<&PairEdge> {
+        self.graph
+            .edge_weight(location.token0, location.token1)?
+            .iter()
+            .find(|pair| pair.id == id)
+    }
+
+    fn edge_mut(&mut self, id: i32, location: &PairLocation) -> Option<&mut PairEdge> {
+        self.graph
+            .edge_weight_mut(location.token0, location.token1)?
+            .iter_mut()
+            .find(|pair| pair.id == id)
+    }
+
+    /// Insert the pair or replace the one with the same id, return `false` if
+    /// its reserves are invalid or its tokens don't pass the filter.
+    fn insert_pair(&mut self, pair: &PairsStreamEntry) -> bool {
+        self.last_block_height = self.last_block_height.max(pair.block_height);
+
+        if !self.filter.allows_pair(pair.token0_id, pair.token1_id) {
+            self.excluded.insert(pair.pair_id);
+            return false;
+        }
+
+        let Some(edge) = PairEdge::from_entry(pair) else {
+            // Truncated reserves are left for `repair-reserves` to fix.
+            return false;
+        };
+
+        self.remove_pair(pair.pair_id);
+
+        match self.graph.edge_weight_mut(pair.token0_id, pair.token1_id) {
+            Some(pairs) => pairs.push(edge),
+            None => {
+                self.graph
+                    .add_edge(pair.token0_id, pair.token1_id, vec![edge]);
+            }
+        }
+
+        self.pairs.insert(
+            pair.pair_id,
+            PairLocation {
+                token0: pair.token0_id,
+                token1: pair.token1_id,
+                block_height: pair.block_height,
+            },
+        );
+        self.pair_ids
+            .insert(pair.pair_address.trim_end().to_string(), pair.pair_id);
+
+        true
+    }
+
+    fn remove_pair(&mut self, id: i32) {
+        let Some(location) = self.pairs.remove(&id) else {
+            return;
+        };
+
+        if let Some(pairs) = self.graph.edge_weight_mut(location.token0, location.token1) {
+            if let Some(index) = pairs.iter().position(|pair| pair.id == id) {
+                let pair = pairs.remove(index);
+                self.pair_ids.remove(pair.address.trim_end());
+            }
+
+            if pairs.is_empty() {
+                self.graph.remove_edge(location.token0, location.token1);
+            }
+        }
+    }
+
+    /// Apply the reserves to the pair in the graph, return `false` if the
+    /// pair is unknown. Reserves older than the ones in the graph and the ones
+    /// of the excluded pairs are ignored.
+    pub fn apply_reserves(&mut self, update: &ReservesUpdate) -> bool {
+        self.last_block_height = self.last_block_height.max(update.block_height);
+
+        if self.excluded.contains(&update.pair) {
+            return true;
+        }
+
+        let Some(location) = self.pairs.get(&update.pair).copied() else {
+            return false;
+        };
+
+        if update.block_height < location.block_height {
+            return true;
+        }
+
+        let (Some(reserve0), Some(reserve1)) = (
+            decimal_to_reserve(&update.reserve0),
+            decimal_to_reserve(&update.reserve1),
+        ) else {
+            return true;
+        };
+
+        if let Some(pair) = self.edge_mut(update.pair, &location) {
+            pair.reserve0 = U256::from(reserve0);
+            pair.reserve1 = U256::from(reserve1);
+        }
+
+        if let Some(location) = self.pairs.get_mut(&update.pair) {
+            location.block_height = update.block_height;
+        }
+
+        true
+    }
+
+    /// Apply the notification, return the id of the pair to load again with
+    /// [`DB::pair_with_reserves`] if it's new or if its reserves were deleted.
+    pub fn apply_notification(&mut self, notification: &ReservesNotification) -> Option<i32> {
+        match notification {
+            ReservesNotification::Updated(update) => {
+                (!self.apply_reserves(update)).then_some(update.pair)
+            }
+            ReservesNotification::Deleted { pair, .. } => {
+                (!self.excluded.contains(pair)).then_some(*pair)
+            }
+        }
+    }
+
+    /// Height of the block to read the reserves from with
+    /// [`DB::reserves_since`], to catch up with the ones stored while the
+    /// notifications weren't listened to.
+    ///
+    /// Reserves of older blocks could be committed after the ones of the
+    /// newer blocks, e.g. by the indexer running next to the follower, so the
+    /// last [`CATCH_UP_DEPTH`] blocks are read again.
+    pub fn catch_up_height(&self) -> i64 {
+        (self.last_block_height - CATCH_UP_DEPTH).max(0)
+    }
+
+    /// Apply the reserves read to catch up, return ids of the pairs to load
+    /// again as they are new.
+    pub fn catch_up(&mut self, updates: &[ReservesUpdate]) -> Vec<i32> {
+        let mut new_pairs: Vec<i32> = updates
+            .iter()
+            .filter(|update| !self.apply_reserves(update))
+            .map(|update| update.pair)
+            .collect();
+
+        new_pairs.sort_unstable();
+        new_pairs.dedup();
+
+        new_pairs
+    }
+
+    /// Insert the pair loaded again, or remove it if it has no reserves
+    /// anymore. Return `true` if the pair is new to the graph.
+    pub fn apply_reloaded_pair(&mut self, pair_id: i32, pair: Option<&PairsStreamEntry>) -> bool {
+        let is_new = !self.pairs.contains_key(&pair_id);
+
+        match pair {
+            Some(pair) => self.insert_pair(pair) && is_new,
+            None => {
+                self.remove_pair(pair_id);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use sqlx::types::BigDecimal;
+
+    use crate::testing;
+
+    use super::*;
+
+    fn update(
+        id: i32,
+        pair: i32,
+        block_height: i64,
+        reserve0: u128,
+        reserve1: u128,
+    ) -> ReservesUpdate {
+        ReservesUpdate {
+            id,
+            pair,
+            block_height,
+            reserve0: BigDecimal::from_str(&reserve0.to_string())
+                .expect("integer is a valid decimal"),
+            reserve1: BigDecimal::from_str(&reserve1.to_string())
+                .expect("integer is a valid decimal"),
+        }
+    }
+
+    fn reserves(graph: &PairGraph, pair: i32) -> (U256, U256) {
+        let (_, _, edge) = graph
+            .pair_by_address(&testing::address(pair))
+            .expect("pair is in the graph");
+
+        (edge.reserve0, edge.reserve1)
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let mut graph = PairGraph::from_pairs(
+            &[testing::stream_entry(1, 1, 2, 100, 100)],
+            GraphFilter::default(),
+        );
+
+        // Reserves of the block 5 were committed after the ones of the block 6.
+        let new_pairs = graph.catch_up(&[
+            update(3, 1, 6, 300, 300),
+            update(2, 1, 5, 200, 200),
+            update(4, 2, 6, 100, 100),
+            update(5, 2, 7, 100, 100),
+        ]);
+
+        assert_eq!(new_pairs, vec![2]);
+        assert_eq!(reserves(&graph, 1), (U256::from(300), U256::from(300)));
+        assert_eq!(graph.catch_up_height(), 0);
+
+        assert!(graph.apply_reloaded_pair(2, Some(&testing::stream_entry(2, 2, 3, 100, 100))));
+        assert!(!graph.apply_reloaded_pair(2, Some(&testing::stream_entry(2, 2, 3, 100, 100))));
+
+        graph.apply_reserves(&update(6, 1, 100, 400, 400));
+        assert_eq!(graph.catch_up_height(), 100 - CATCH_UP_DEPTH);
+    }
+
+    #[test]
+    fn test_apply_notification() {
+        let mut graph = PairGraph::from_pairs(
+            &[testing::stream_entry(1, 1, 2, 100, 100)],
+            GraphFilter::default(),
+        );
+
+        let updated = ReservesNotification::Updated(update(2, 1, 2, 200, 100));
+        assert_eq!(graph.apply_notification(&updated), None);
+        assert_eq!(reserves(&graph, 1), (U256::from(200), U256::from(100)));
+
+        let unknown = ReservesNotification::Updated(update(3, 2, 2, 100, 100));
+        assert_eq!(graph.apply_notification(&unknown), Some(2));
+
+        let deleted = ReservesNotification::Deleted { id: 2, pair: 1 };
+        assert_eq!(graph.apply_notification(&deleted), Some(1));
+
+        assert!(!graph.apply_reloaded_pair(1, None));
+        assert!(graph.pair_by_address(&testing::address(1)).is_none());
+    }
+}
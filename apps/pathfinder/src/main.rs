@@ -3,8 +3,16 @@ use color_eyre::eyre;
 
 use crate::cli::Cli;
 
-mod cli;
 mod actions;
+mod calldata;
+mod cli;
+mod filters;
+mod graph;
+mod math;
+mod pricing;
+mod server;
+#[cfg(test)]
+mod testing;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> eyre::Result<()> {
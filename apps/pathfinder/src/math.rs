@@ -0,0 +1,318 @@
+//! Exact integer swap math of the Uniswap V2 pairs, which reproduces the
+//! rounding of `UniswapV2Library`, so quotes match what the transaction
+//! actually receives.
+
+use config::Fee;
+use ethers::types::{U256, U512};
+
+/// Denominator of the amounts in basis points.
+pub const BPS_DENOMINATOR: u32 = 10_000;
+
+/// Return the maximum output amount of the swap for the given input amount,
+/// as `UniswapV2Library.getAmountOut` does:
+///
+/// $$
+/// \text{amountOut} = \left\lfloor \frac{\text{amountIn} \times \text{feeNumerator} \times \text{reserveOut}}{\text{reserveIn} \times \text{feeDenominator} + \text{amountIn} \times \text{feeNumerator}} \right\rfloor
+/// $$
+///
+/// Return `None` in the cases the library reverts: zero input amount or
+/// reserves, or overflow.
+pub fn get_amount_out(
+    amount_in: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    fee: Fee,
+) -> Option<U256> {
+    if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
+        return None;
+    }
+
+    let amount_in_with_fee = amount_in.checked_mul(fee.numerator.into())?;
+    let numerator = amount_in_with_fee.checked_mul(reserve_out)?;
+    let denominator = reserve_in
+        .checked_mul(fee.denominator.into())?
+        .checked_add(amount_in_with_fee)?;
+
+    Some(numerator / denominator)
+}
+
+/// Return the input amount required to receive the given output amount, as
+/// `UniswapV2Library.getAmountIn` does:
+///
+/// $$
+/// \text{amountIn} = \left\lfloor \frac{\text{reserveIn} \times \text{amountOut} \times \text{feeDenominator}}{(\text{reserveOut} - \text{amountOut}) \times \text{feeNumerator}} \right\rfloor + 1
+/// $$
+///
+/// Return `None` in the cases the library reverts: zero output amount or
+/// reserves, output not less than the reserve, or overflow.
+pub fn get_amount_in(
+    amount_out: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    fee: Fee,
+) -> Option<U256> {
+    if amount_out.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
+        return None;
+    }
+
+    let numerator = reserve_in
+        .checked_mul(amount_out)?
+        .checked_mul(fee.denominator.into())?;
+    let denominator = reserve_out
+        .checked_sub(amount_out)?
+        .checked_mul(fee.numerator.into())?;
+
+    if denominator.is_zero() {
+        return None;
+    }
+
+    (numerator / denominator).checked_add(U256::one())
+}
+
+/// Return the output for a unit of input of an infinitely small swap through
+/// the pair: the reserves ratio with the fee applied.
+pub fn spot_rate(reserve_in: U256, reserve_out: U256, fee: Fee) -> f64 {
+    to_f64(reserve_out) / to_f64(reserve_in) * fee.numerator as f64 / fee.denominator as f64
+}
+
+/// Return the share of the output lost to the reserves moved by the swaps,
+/// compared to the spot rate of the pair or the route. Fees aren't counted as
+/// the impact.
+pub fn price_impact(amount_in: U256, amount_out: U256, spot_rate: f64) -> f64 {
+    1.0 - to_f64(amount_out) / (to_f64(amount_in) * spot_rate)
+}
+
+/// Return the smallest output accepted with the slippage tolerance in basis
+/// points, rounded down.
+pub fn min_amount_out(amount_out: U256, slippage_bps: u32) -> U256 {
+    let bps = U256::from(BPS_DENOMINATOR - slippage_bps.min(BPS_DENOMINATOR));
+
+    (amount_out.full_mul(bps) / U512::from(BPS_DENOMINATOR))
+        .try_into()
+        .expect("result isn't bigger than the amount")
+}
+
+/// Return the biggest input accepted with the slippage tolerance in basis
+/// points, rounded up, or `None` on overflow.
+pub fn max_amount_in(amount_in: U256, slippage_bps: u32) -> Option<U256> {
+    let bps = U256::from(BPS_DENOMINATOR + slippage_bps);
+    let denominator = U512::from(BPS_DENOMINATOR);
+
+    let result = (amount_in.full_mul(bps) + denominator - 1) / denominator;
+
+    result.try_into().ok()
+}
+
+/// Convert the amount to `f64`, losing precision beyond 53 bits.
+pub fn to_f64(value: U256) -> f64 {
+    value
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |result, limb| result * 2f64.powi(64) + *limb as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bindings::{
+        erc20_mock::ERC20Mock, uniswap_v2_factory::UniswapV2Factory, uniswap_v2_pair::UniswapV2Pair,
+    };
+    use color_eyre::eyre;
+    use ethers::{
+        providers::{Http, Middleware, Provider},
+        types::Address,
+    };
+
+    use crate::testing;
+
+    use super::*;
+
+    const FEE: Fee = Fee {
+        numerator: 997,
+        denominator: 1000,
+    };
+
+    #[test]
+    fn test_get_amount_out() {
+        let amount_out = get_amount_out(1000.into(), 1_000_000.into(), 2_000_000.into(), FEE);
+
+        // 1000 * 997 * 2000000 / (1000000 * 1000 + 1000 * 997) = 1992.01...
+        assert_eq!(amount_out, Some(1992.into()));
+    }
+
+    #[test]
+    fn test_get_amount_in() {
+        let amount_in = get_amount_in(1992.into(), 1_000_000.into(), 2_000_000.into(), FEE);
+
+        // 1000000 * 1992 * 1000 / ((2000000 - 1992) * 997) + 1 = 999.99... + 1
+        assert_eq!(amount_in, Some(1000.into()));
+    }
+
+    #[test]
+    fn test_slippage() {
+        assert_eq!(min_amount_out(1999.into(), 50), 1989.into());
+        assert_eq!(min_amount_out(1999.into(), BPS_DENOMINATOR), U256::zero());
+        assert_eq!(max_amount_in(1999.into(), 50), Some(2009.into()));
+        assert_eq!(max_amount_in(U256::MAX, 50), None);
+    }
+
+    #[test]
+    fn test_to_f64() {
+        assert_eq!(to_f64(U256::zero()), 0.0);
+        assert_eq!(to_f64(U256::from(1_000_000u64)), 1e6);
+        assert_eq!(to_f64(U256::one() << 100), 2f64.powi(100));
+    }
+
+    #[test]
+    fn test_reverting_cases() {
+        let reserve = U256::from(1_000_000);
+
+        assert_eq!(get_amount_out(U256::zero(), reserve, reserve, FEE), None);
+        assert_eq!(get_amount_out(reserve, U256::zero(), reserve, FEE), None);
+        assert_eq!(get_amount_out(U256::MAX, reserve, reserve, FEE), None);
+        assert_eq!(get_amount_in(U256::zero(), reserve, reserve, FEE), None);
+        assert_eq!(get_amount_in(reserve, reserve, reserve, FEE), None);
+        assert_eq!(get_amount_in(reserve + 1, reserve, reserve, FEE), None);
+    }
+
+    /// Deterministic xorshift generator, so the failing cases could be
+    /// reproduced.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Random value with random number of bits in `min_bits..max_bits`,
+        /// to cover the values of different magnitudes.
+        fn amount(&mut self, min_bits: u64, max_bits: u64) -> U256 {
+            let bits = min_bits + self.next() % (max_bits - min_bits);
+            let value = U256::from(self.next()) << 64 | U256::from(self.next());
+
+            (value >> (128 - bits)) | U256::one() << (bits - 1)
+        }
+    }
+
+    type Client = Provider<Http>;
+
+    /// Deploy a new pair with the given reserves and return it with its
+    /// tokens.
+    async fn deploy_pair(
+        client: Arc<Client>,
+        reserve0: U256,
+        reserve1: U256,
+    ) -> eyre::Result<(UniswapV2Pair<Client>, ERC20Mock<Client>, ERC20Mock<Client>)> {
+        let sender = client.default_sender().expect("sender is set");
+
+        let token_a = ERC20Mock::deploy(client.clone(), ("A".to_string(), "A".to_string(), 18u8))?
+            .send()
+            .await?;
+        let token_b = ERC20Mock::deploy(client.clone(), ("B".to_string(), "B".to_string(), 18u8))?
+            .send()
+            .await?;
+        let factory = UniswapV2Factory::deploy(client.clone(), sender)?
+            .send()
+            .await?;
+
+        factory
+            .create_pair(token_a.address(), token_b.address())
+            .send()
+            .await?
+            .await?;
+        let pair_address = factory
+            .get_pair(token_a.address(), token_b.address())
+            .call()
+            .await?;
+        let pair = UniswapV2Pair::new(pair_address, client.clone());
+
+        let (token0, token1) = if pair.token_0().call().await? == token_a.address() {
+            (token_a, token_b)
+        } else {
+            (token_b, token_a)
+        };
+
+        token0.mint(pair_address, reserve0).send().await?.await?;
+        token1.mint(pair_address, reserve1).send().await?.await?;
+        pair.sync().send().await?.await?;
+
+        Ok((pair, token0, token1))
+    }
+
+    /// Compares the math with the deployed pairs on random reserves and
+    /// amounts: the swap with the quoted amounts must succeed, and the one
+    /// with a single unit more output must fail.
+    ///
+    /// Requires a local Anvil node at `ETH_RPC_URL`:
+    ///
+    /// ```sh
+    /// anvil & cargo test -p pathfinder -- --ignored
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires a local Anvil node"]
+    async fn test_matches_deployed_pair() -> eyre::Result<()> {
+        const CASES: usize = 32;
+
+        let provider = testing::anvil_provider()?;
+        let sender = provider.get_accounts().await?[0];
+        let client = Arc::new(provider.with_sender(sender));
+        let recipient = Address::random();
+
+        let mut rng = Rng(0x5eed_1234_abcd_9876);
+
+        for case in 0..CASES {
+            // Balances of the pair can't exceed `uint112`, so reserves and
+            // inputs are kept below it together.
+            let reserve0 = rng.amount(16, 110);
+            let reserve1 = rng.amount(16, 110);
+
+            // Exact input: the output must be the maximum one allowed.
+            let amount_in = rng.amount(1, 100);
+            let amount_out = get_amount_out(amount_in, reserve0, reserve1, FEE)
+                .ok_or_else(|| eyre::eyre!("Case {}: no output", case))?;
+
+            let (pair, token0, _) = deploy_pair(client.clone(), reserve0, reserve1).await?;
+            token0.mint(pair.address(), amount_in).send().await?.await?;
+
+            if !amount_out.is_zero() {
+                pair.swap(U256::zero(), amount_out, recipient, Default::default())
+                    .call()
+                    .await
+                    .map_err(|err| eyre::eyre!("Case {}: quoted output failed: {}", case, err))?;
+            }
+            let result = pair
+                .swap(U256::zero(), amount_out + 1, recipient, Default::default())
+                .call()
+                .await;
+            assert!(
+                result.is_err(),
+                "Case {}: output above the quote succeeded",
+                case
+            );
+
+            // Exact output: the quoted input must be enough.
+            let amount_out = rng.amount(1, 112) % reserve1;
+            let Some(amount_in) = get_amount_in(amount_out, reserve0, reserve1, FEE) else {
+                continue;
+            };
+            if reserve0 + amount_in >= U256::one() << 112 {
+                continue;
+            }
+
+            let (pair, token0, _) = deploy_pair(client.clone(), reserve0, reserve1).await?;
+            token0.mint(pair.address(), amount_in).send().await?.await?;
+
+            pair.swap(U256::zero(), amount_out, recipient, Default::default())
+                .call()
+                .await
+                .map_err(|err| eyre::eyre!("Case {}: quoted input failed: {}", case, err))?;
+        }
+
+        Ok(())
+    }
+}
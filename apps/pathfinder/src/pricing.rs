@@ -0,0 +1,249 @@
+//! Prices of the tokens derived from the reserves of the pairs.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use database::tokens::TokenEntry;
+
+use crate::{actions::find::PairsGraph, math};
+
+/// Price of the token taken from the most liquid path to the base token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPrice {
+    /// Price of a whole token in whole base tokens.
+    pub price_base: f64,
+
+    /// Price of a whole token in USD, if a stablecoin is reachable.
+    pub price_usd: Option<f64>,
+
+    /// Smallest liquidity of the pairs on the path, in whole base tokens.
+    pub liquidity: f64,
+}
+
+/// Token reached by the search with the liquidity of its path, ordered by the
+/// liquidity to pop the most liquid one first.
+struct Reached {
+    token: i32,
+    liquidity: f64,
+}
+
+impl PartialEq for Reached {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Reached {}
+
+impl PartialOrd for Reached {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Reached {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.liquidity.total_cmp(&other.liquidity)
+    }
+}
+
+/// Return the decimals of the tokens which can be trusted, leaving out the
+/// guessed ones, so the tokens with the guessed decimals aren't priced.
+pub fn known_decimals<'a>(tokens: impl IntoIterator<Item = &'a TokenEntry>) -> HashMap<i32, u32> {
+    tokens
+        .into_iter()
+        .filter(|token| !token.has_unknown_metadata())
+        .filter_map(|token| Some((token.id, u32::try_from(token.decimals).ok()?)))
+        .collect()
+}
+
+/// Compute prices of the tokens reachable from the `base` token.
+///
+/// Each token is priced along the path with the biggest bottleneck liquidity
+/// (widest path), where liquidity of a pair is the value of its reserve of the
+/// already priced token, so prices aren't taken from thin pairs when deeper
+/// ones are available. The price in USD is taken from the most liquid of the
+/// `stablecoins`, assuming it's worth exactly one dollar.
+///
+/// Tokens without known `decimals` are skipped.
+pub fn price_tokens(
+    graph: &PairsGraph,
+    decimals: &HashMap<i32, u32>,
+    base: i32,
+    stablecoins: &[i32],
+) -> HashMap<i32, TokenPrice> {
+    let mut prices: HashMap<i32, TokenPrice> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    prices.insert(
+        base,
+        TokenPrice {
+            price_base: 1.0,
+            price_usd: None,
+            liquidity: f64::INFINITY,
+        },
+    );
+    queue.push(Reached {
+        token: base,
+        liquidity: f64::INFINITY,
+    });
+
+    let whole = |token: i32, amount: f64| {
+        decimals
+            .get(&token)
+            .map(|decimals| amount / 10f64.powi(*decimals as i32))
+    };
+
+    while let Some(Reached { token, liquidity }) = queue.pop() {
+        let price = &prices[&token];
+        // Skip outdated entries of tokens reached by a wider path later.
+        if liquidity < price.liquidity {
+            continue;
+        }
+        let price_base = price.price_base;
+
+        for (_, neighbor, pairs) in graph.edges(token) {
+            for pair in pairs {
+                let (reserve, neighbor_reserve) = pair.reserves_from(token);
+
+                let (Some(reserve), Some(neighbor_reserve)) = (
+                    whole(token, math::to_f64(reserve)),
+                    whole(neighbor, math::to_f64(neighbor_reserve)),
+                ) else {
+                    continue;
+                };
+
+                if reserve == 0.0 || neighbor_reserve == 0.0 {
+                    continue;
+                }
+
+                let path_liquidity = liquidity.min(reserve * price_base);
+                let is_wider = prices
+                    .get(&neighbor)
+                    .map_or(true, |known| known.liquidity < path_liquidity);
+
+                if is_wider {
+                    prices.insert(
+                        neighbor,
+                        TokenPrice {
+                            price_base: price_base * reserve / neighbor_reserve,
+                            price_usd: None,
+                            liquidity: path_liquidity,
+                        },
+                    );
+                    queue.push(Reached {
+                        token: neighbor,
+                        liquidity: path_liquidity,
+                    });
+                }
+            }
+        }
+    }
+
+    let usd_per_base = stablecoins
+        .iter()
+        .filter_map(|stablecoin| prices.get(stablecoin))
+        .max_by(|a, b| a.liquidity.total_cmp(&b.liquidity))
+        .map(|stablecoin| 1.0 / stablecoin.price_base);
+
+    if let Some(usd_per_base) = usd_per_base {
+        for price in prices.values_mut() {
+            price.price_usd = Some(price.price_base * usd_per_base);
+        }
+    }
+
+    prices
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing;
+
+    use super::*;
+
+    const WETH: i32 = 1;
+    const USDC: i32 = 2;
+    const TOKEN: i32 = 3;
+    const UNKNOWN: i32 = 4;
+    const THIN: i32 = 5;
+
+    const E18: u128 = 1_000_000_000_000_000_000;
+    const E6: u128 = 1_000_000;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_prices_propagate() {
+        let graph = testing::graph(&[
+            // 2000 USDC per WETH.
+            (1, WETH, USDC, 1_000 * E18, 2_000_000 * E6),
+            // 2 USDC per token, which has no pair with WETH.
+            (2, USDC, TOKEN, 2_000_000 * E6, 1_000_000 * E18),
+            (3, WETH, UNKNOWN, E18, E18),
+            // The thin pair is ignored in favour of the path through the
+            // deep pairs.
+            (4, WETH, THIN, E18, E18),
+            (5, TOKEN, THIN, 1_000_000 * E18, 100_000 * E18),
+        ]);
+        let decimals = HashMap::from([(WETH, 18), (USDC, 6), (TOKEN, 18), (THIN, 18)]);
+
+        let prices = price_tokens(&graph, &decimals, WETH, &[USDC]);
+
+        assert_eq!(prices[&WETH].price_base, 1.0);
+        assert_close(prices[&WETH].price_usd.unwrap(), 2_000.0);
+
+        assert_close(prices[&USDC].price_base, 0.0005);
+        assert_close(prices[&USDC].price_usd.unwrap(), 1.0);
+        assert_close(prices[&USDC].liquidity, 1_000.0);
+
+        assert_close(prices[&TOKEN].price_base, 0.001);
+        assert_close(prices[&TOKEN].price_usd.unwrap(), 2.0);
+        assert_close(prices[&TOKEN].liquidity, 1_000.0);
+
+        assert_close(prices[&THIN].price_base, 0.01);
+        assert_close(prices[&THIN].price_usd.unwrap(), 20.0);
+
+        // Tokens without decimals can't be priced.
+        assert!(!prices.contains_key(&UNKNOWN));
+    }
+
+    #[test]
+    fn test_prices_without_stablecoins() {
+        let graph = testing::graph(&[(1, WETH, TOKEN, E18, 4 * E18)]);
+        let decimals = HashMap::from([(WETH, 18), (TOKEN, 18)]);
+
+        let prices = price_tokens(&graph, &decimals, WETH, &[USDC]);
+
+        assert_close(prices[&TOKEN].price_base, 0.25);
+        assert!(prices.values().all(|price| price.price_usd.is_none()));
+    }
+
+    #[test]
+    fn test_guessed_decimals_are_not_priced() {
+        let graph = testing::graph(&[(1, WETH, TOKEN, E18, 4 * E18), (2, WETH, UNKNOWN, E18, E18)]);
+
+        let mut guessed = testing::token(UNKNOWN, "UNK", 18);
+        guessed.metadata_status = "guessed".to_string();
+        let tokens = [
+            testing::token(WETH, "WETH", 18),
+            testing::token(TOKEN, "TKN", 18),
+            guessed,
+        ];
+
+        let decimals = known_decimals(&tokens);
+        assert_eq!(decimals, HashMap::from([(WETH, 18), (TOKEN, 18)]));
+
+        let prices = price_tokens(&graph, &decimals, WETH, &[]);
+        assert!(prices.contains_key(&TOKEN));
+        assert!(!prices.contains_key(&UNKNOWN));
+    }
+}
@@ -0,0 +1,793 @@
+//! JSON HTTP API answering quotes from the pairs graph kept in memory.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use color_eyre::eyre;
+use config::GraphFilters;
+use database::{format_address, tokens::TokenEntry, PairsStreamEntry, DB};
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    actions::find::{
+        factory_names, find_best_route, find_best_route_exact_out, find_top_routes, Route, MAX_HOPS,
+    },
+    filters::GraphFilter,
+    graph::PairGraph,
+    math,
+};
+
+pub type SharedState = Arc<RwLock<GraphState>>;
+
+/// Pair loaded again from the database with the tokens and the factories the
+/// state doesn't know yet, loaded without holding the lock of the state.
+pub struct ReloadedPair {
+    pub pair_id: i32,
+
+    /// The pair with its latest reserves, `None` if it has no reserves
+    /// anymore.
+    pub pair: Option<PairsStreamEntry>,
+
+    pub tokens: Vec<TokenEntry>,
+
+    /// Names of all the factories, if the factory of the pair is new.
+    pub factory_names: Option<HashMap<i32, String>>,
+}
+
+/// Maximum number of the alternative routes returned at once.
+const MAX_ROUTES: usize = 10;
+
+/// Pairs graph with the tokens and factories it refers to.
+pub struct GraphState {
+    pub graph: PairGraph,
+
+    /// Tokens by their ids.
+    pub tokens: HashMap<i32, TokenEntry>,
+
+    /// Ids of the tokens by their addresses.
+    pub token_ids: HashMap<String, i32>,
+
+    /// Names of the factories by their ids.
+    pub factory_names: HashMap<i32, String>,
+}
+
+impl GraphState {
+    /// Load the graph of the pairs passing the filter with the latest
+    /// reserves from the database.
+    pub async fn load(
+        database: &DB,
+        filters: &GraphFilters,
+        base_token: Option<Address>,
+    ) -> eyre::Result<Self> {
+        let mut txn = database.pool().begin().await?;
+
+        let filter = GraphFilter::load(&mut txn, filters, base_token).await?;
+
+        let graph = PairGraph::load(&mut txn, filter, None).await?;
+        let tokens = DB::tokens(&mut txn).await?;
+        let factory_names = factory_names(&mut txn).await?;
+
+        Ok(Self::new(graph, tokens, factory_names))
+    }
+
+    /// Build the state of the graph with the tokens and the names of the
+    /// factories it refers to.
+    pub fn new(
+        graph: PairGraph,
+        tokens: Vec<TokenEntry>,
+        factory_names: HashMap<i32, String>,
+    ) -> Self {
+        let mut state = Self {
+            graph,
+            tokens: HashMap::new(),
+            token_ids: HashMap::new(),
+            factory_names,
+        };
+        state.add_tokens(tokens);
+
+        state
+    }
+
+    fn add_tokens(&mut self, tokens: Vec<TokenEntry>) {
+        for token in tokens {
+            self.token_ids
+                .insert(token.address.trim_end().to_string(), token.id);
+            self.tokens.insert(token.id, token);
+        }
+    }
+
+    /// Return ids of the tokens of the pair which aren't known, and whether
+    /// its factory isn't known.
+    pub fn missing_metadata(&self, pair: &PairsStreamEntry) -> (Vec<i32>, bool) {
+        let tokens = [pair.token0_id, pair.token1_id]
+            .into_iter()
+            .filter(|token| !self.tokens.contains_key(token))
+            .collect();
+
+        (tokens, !self.factory_names.contains_key(&pair.factory_id))
+    }
+
+    /// Apply the pair loaded again together with the metadata it brings.
+    pub fn apply_reloaded_pair(&mut self, reloaded: ReloadedPair) {
+        self.add_tokens(reloaded.tokens);
+        if let Some(factory_names) = reloaded.factory_names {
+            self.factory_names = factory_names;
+        }
+
+        self.graph
+            .apply_reloaded_pair(reloaded.pair_id, reloaded.pair.as_ref());
+    }
+
+    fn token_id(&self, address: Address) -> Result<i32, ApiError> {
+        self.token_ids
+            .get(&format_address(address))
+            .copied()
+            .ok_or_else(|| ApiError::NotFound(format!("Token {:?} not found", address)))
+    }
+
+    fn token(&self, id: i32) -> TokenResponse {
+        match self.tokens.get(&id) {
+            Some(token) => TokenResponse::from(token),
+            // Pairs reference only the stored tokens, but the graph and the
+            // tokens are loaded in different queries.
+            None => TokenResponse {
+                address: String::new(),
+                name: String::new(),
+                symbol: id.to_string(),
+                decimals: 0,
+                metadata_status: String::new(),
+                transfer_tax_bps: None,
+                rebasing: None,
+            },
+        }
+    }
+
+    /// Resolve the tokens and parse the amount of the request.
+    fn parse_request(
+        &self,
+        from: Address,
+        to: Address,
+        amount: &str,
+    ) -> Result<(i32, i32, U256), ApiError> {
+        let from = self.token_id(from)?;
+        let to = self.token_id(to)?;
+
+        if from == to {
+            return Err(ApiError::BadRequest(
+                "Start and goal tokens are the same".to_string(),
+            ));
+        }
+
+        let amount = U256::from_dec_str(amount)
+            .map_err(|err| ApiError::BadRequest(format!("Invalid amount: {}", err)))?;
+
+        Ok((from, to, amount))
+    }
+
+    /// Find the best route for the quote request.
+    fn find_route(&self, query: &QuoteQuery) -> Result<Route, ApiError> {
+        let (from, to, amount) = self.parse_request(query.from, query.to, &query.amount)?;
+
+        if query.slippage_bps > math::BPS_DENOMINATOR {
+            return Err(ApiError::BadRequest(
+                "Slippage must be from 0 to 10000 basis points".to_string(),
+            ));
+        }
+
+        let route = if query.exact_out {
+            find_best_route_exact_out(self.graph.graph(), from, to, amount, MAX_HOPS)
+        } else {
+            find_best_route(self.graph.graph(), from, to, amount, MAX_HOPS)
+        };
+
+        route.ok_or_else(|| ApiError::NotFound("No route found".to_string()))
+    }
+}
+
+/// Build the router of the API.
+pub fn router(state: SharedState) -> Router {
+    Router::new()
+        .route("/quote", get(quote))
+        .route("/route", get(route))
+        .route("/routes", get(routes))
+        .route("/tokens", get(tokens))
+        .route("/pairs/:address", get(pair))
+        .with_state(state)
+}
+
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            ApiError::BadRequest(error) => (StatusCode::BAD_REQUEST, error),
+            ApiError::NotFound(error) => (StatusCode::NOT_FOUND, error),
+        };
+
+        (status, Json(ErrorResponse { error })).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuoteQuery {
+    pub from: Address,
+    pub to: Address,
+
+    /// Input amount in the smallest units, or the output one for the exact
+    /// output quotes. Decimal string, as amounts don't fit into JSON numbers.
+    pub amount: String,
+
+    /// Treat `amount` as the exact output and find the input for it.
+    #[serde(default)]
+    pub exact_out: bool,
+
+    /// Slippage tolerance in basis points for the minimum output, or the
+    /// maximum input for the exact output quotes.
+    #[serde(default = "default_slippage_bps")]
+    pub slippage_bps: u32,
+}
+
+fn default_slippage_bps() -> u32 {
+    50
+}
+
+impl QuoteQuery {
+    /// Return the minimum output of the exact input quotes or the maximum
+    /// input of the exact output ones, accepted with the slippage.
+    fn slippage_bounds(&self, route: &Route) -> Result<(Option<String>, Option<String>), ApiError> {
+        if self.exact_out {
+            let max_amount_in = math::max_amount_in(route.amount_in, self.slippage_bps)
+                .ok_or_else(|| ApiError::BadRequest("Maximum input overflows".to_string()))?;
+
+            Ok((None, Some(max_amount_in.to_string())))
+        } else {
+            let min_amount_out = math::min_amount_out(route.amount_out, self.slippage_bps);
+
+            Ok((Some(min_amount_out.to_string()), None))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoutesQuery {
+    pub from: Address,
+    pub to: Address,
+
+    /// Input amount in the smallest units as a decimal string.
+    pub amount: String,
+
+    /// Number of the routes to find, at most [`MAX_ROUTES`].
+    #[serde(default = "default_routes_count")]
+    pub count: usize,
+}
+
+fn default_routes_count() -> usize {
+    3
+}
+
+#[derive(Serialize)]
+struct QuoteResponse {
+    amount_in: String,
+    amount_out: String,
+
+    /// Smallest output accepted with the slippage, for the exact input
+    /// quotes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_amount_out: Option<String>,
+
+    /// Biggest input accepted with the slippage, for the exact output quotes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_amount_in: Option<String>,
+
+    /// Addresses of the tokens on the route.
+    tokens: Vec<String>,
+
+    /// Addresses of the pairs of each hop.
+    pairs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct AlternativeRouteResponse {
+    amount_out: String,
+
+    /// Share of the output lost to the price impact, fees excluded.
+    price_impact: f64,
+
+    hops: usize,
+    tokens: Vec<String>,
+    pairs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RouteResponse {
+    amount_in: String,
+    amount_out: String,
+
+    /// Smallest output accepted with the slippage, for the exact input
+    /// quotes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_amount_out: Option<String>,
+
+    /// Biggest input accepted with the slippage, for the exact output quotes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_amount_in: Option<String>,
+
+    /// Price of a whole input token in whole output tokens from the reserves,
+    /// without the fees.
+    mid_price: f64,
+
+    /// Price of a whole input token in whole output tokens the route gives.
+    execution_price: f64,
+
+    /// Share of the output lost to the price impact, fees excluded.
+    price_impact: f64,
+
+    hops: Vec<HopResponse>,
+}
+
+#[derive(Serialize)]
+struct HopResponse {
+    token_in: TokenResponse,
+    token_out: TokenResponse,
+    pair: String,
+    factory: Option<String>,
+    amount_in: String,
+    amount_out: String,
+    price_impact: f64,
+}
+
+#[derive(Serialize)]
+struct TokenResponse {
+    address: String,
+    name: String,
+    symbol: String,
+    decimals: i32,
+    metadata_status: String,
+    transfer_tax_bps: Option<i32>,
+    rebasing: Option<bool>,
+}
+
+impl From<&TokenEntry> for TokenResponse {
+    fn from(token: &TokenEntry) -> Self {
+        Self {
+            address: token.address.trim_end().to_string(),
+            name: token.name.clone(),
+            symbol: token.symbol.clone(),
+            decimals: token.decimals,
+            metadata_status: token.metadata_status.clone(),
+            transfer_tax_bps: token.transfer_tax_bps,
+            rebasing: token.rebasing,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PairResponse {
+    address: String,
+    factory: Option<String>,
+    token0: TokenResponse,
+    token1: TokenResponse,
+    reserve0: String,
+    reserve1: String,
+    fee_numerator: u32,
+    fee_denominator: u32,
+}
+
+/// Amounts and addresses of the best route.
+async fn quote(
+    State(state): State<SharedState>,
+    Query(query): Query<QuoteQuery>,
+) -> Result<Json<QuoteResponse>, ApiError> {
+    let state = state.read().await;
+    let route = state.find_route(&query)?;
+    let (min_amount_out, max_amount_in) = query.slippage_bounds(&route)?;
+
+    Ok(Json(QuoteResponse {
+        amount_in: route.amount_in.to_string(),
+        amount_out: route.amount_out.to_string(),
+        min_amount_out,
+        max_amount_in,
+        tokens: route
+            .tokens
+            .iter()
+            .map(|token| state.token(*token).address)
+            .collect(),
+        pairs: route
+            .pairs
+            .iter()
+            .map(|pair| pair.address.trim_end().to_string())
+            .collect(),
+    }))
+}
+
+/// Best route with the tokens, pairs and amounts of each hop.
+async fn route(
+    State(state): State<SharedState>,
+    Query(query): Query<QuoteQuery>,
+) -> Result<Json<RouteResponse>, ApiError> {
+    let state = state.read().await;
+    let route = state.find_route(&query)?;
+    let (min_amount_out, max_amount_in) = query.slippage_bounds(&route)?;
+
+    let amounts = route.amounts();
+    let hops = route
+        .pairs
+        .iter()
+        .zip(route.hop_price_impacts())
+        .enumerate()
+        .map(|(i, (pair, price_impact))| HopResponse {
+            token_in: state.token(route.tokens[i]),
+            token_out: state.token(route.tokens[i + 1]),
+            pair: pair.address.trim_end().to_string(),
+            factory: state.factory_names.get(&pair.factory_id).cloned(),
+            amount_in: amounts[i].to_string(),
+            amount_out: amounts[i + 1].to_string(),
+            price_impact,
+        })
+        .collect();
+
+    let decimals_in = state.token(route.tokens[0]).decimals;
+    let decimals_out = state.token(route.tokens[route.tokens.len() - 1]).decimals;
+    let decimals_scale = 10f64.powi(decimals_in - decimals_out);
+
+    Ok(Json(RouteResponse {
+        amount_in: route.amount_in.to_string(),
+        amount_out: route.amount_out.to_string(),
+        min_amount_out,
+        max_amount_in,
+        mid_price: route.mid_price() * decimals_scale,
+        execution_price: math::to_f64(route.amount_out) / math::to_f64(route.amount_in)
+            * decimals_scale,
+        price_impact: route.price_impact(),
+        hops,
+    }))
+}
+
+/// Best distinct routes from the best one, to fall back to if the pairs of
+/// the best route change before the swap is included.
+async fn routes(
+    State(state): State<SharedState>,
+    Query(query): Query<RoutesQuery>,
+) -> Result<Json<Vec<AlternativeRouteResponse>>, ApiError> {
+    let state = state.read().await;
+    let (from, to, amount) = state.parse_request(query.from, query.to, &query.amount)?;
+
+    if query.count == 0 || query.count > MAX_ROUTES {
+        return Err(ApiError::BadRequest(format!(
+            "Count must be from 1 to {}",
+            MAX_ROUTES
+        )));
+    }
+
+    let routes = find_top_routes(state.graph.graph(), from, to, amount, query.count, MAX_HOPS);
+    if routes.is_empty() {
+        return Err(ApiError::NotFound("No route found".to_string()));
+    }
+
+    let routes = routes
+        .iter()
+        .map(|route| AlternativeRouteResponse {
+            amount_out: route.amount_out.to_string(),
+            price_impact: route.price_impact(),
+            hops: route.pairs.len(),
+            tokens: route
+                .tokens
+                .iter()
+                .map(|token| state.token(*token).address)
+                .collect(),
+            pairs: route
+                .pairs
+                .iter()
+                .map(|pair| pair.address.trim_end().to_string())
+                .collect(),
+        })
+        .collect();
+
+    Ok(Json(routes))
+}
+
+/// All the known tokens.
+async fn tokens(State(state): State<SharedState>) -> Json<Vec<TokenResponse>> {
+    let state = state.read().await;
+
+    let mut tokens: Vec<&TokenEntry> = state.tokens.values().collect();
+    tokens.sort_by_key(|token| token.id);
+
+    Json(tokens.into_iter().map(TokenResponse::from).collect())
+}
+
+/// Tokens, fee and the latest reserves of the pair.
+async fn pair(
+    State(state): State<SharedState>,
+    Path(address): Path<Address>,
+) -> Result<Json<PairResponse>, ApiError> {
+    let state = state.read().await;
+
+    let (token0, token1, pair) = state
+        .graph
+        .pair_by_address(&format_address(address))
+        .ok_or_else(|| ApiError::NotFound(format!("Pair {:?} not found", address)))?;
+
+    Ok(Json(PairResponse {
+        address: pair.address.trim_end().to_string(),
+        factory: state.factory_names.get(&pair.factory_id).cloned(),
+        token0: state.token(token0),
+        token1: state.token(token1),
+        reserve0: pair.reserve0.to_string(),
+        reserve1: pair.reserve1.to_string(),
+        fee_numerator: pair.fee.numerator,
+        fee_denominator: pair.fee.denominator,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::Request,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::testing;
+
+    async fn get_json(app: &Router, uri: &str) -> eyre::Result<(StatusCode, serde_json::Value)> {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty())?)
+            .await?;
+
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await?;
+
+        Ok((status, serde_json::from_slice(&body)?))
+    }
+
+    const WETH: i32 = 1;
+    const USDC: i32 = 2;
+    const TOKEN: i32 = 3;
+    const UNPAIRED: i32 = 4;
+
+    const E18: u128 = 1_000_000_000_000_000_000;
+    const E6: u128 = 1_000_000;
+
+    /// Router over the graph of WETH/USDC and USDC/TOKEN pairs, with a token
+    /// without pairs.
+    fn test_app() -> Router {
+        let pairs = [
+            testing::stream_entry(1, WETH, USDC, 1_000 * E18, 2_000_000 * E6),
+            testing::stream_entry(2, USDC, TOKEN, 2_000_000 * E6, 1_000_000 * E18),
+        ];
+        let tokens = vec![
+            testing::token(WETH, "WETH", 18),
+            testing::token(USDC, "USDC", 6),
+            testing::token(TOKEN, "TKN", 18),
+            testing::token(UNPAIRED, "NONE", 18),
+        ];
+
+        let graph = PairGraph::from_pairs(&pairs, GraphFilter::default());
+        let state = GraphState::new(graph, tokens, HashMap::from([(1, "Uniswap".to_string())]));
+
+        router(Arc::new(RwLock::new(state)))
+    }
+
+    fn address(id: i32) -> String {
+        testing::token(id, "", 0).address
+    }
+
+    #[tokio::test]
+    async fn test_quotes() -> eyre::Result<()> {
+        let app = test_app();
+        let (weth, usdc, token) = (address(WETH), address(USDC), address(TOKEN));
+
+        let (status, quote) = get_json(
+            &app,
+            &format!("/quote?from={}&to={}&amount={}", weth, token, E18),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(quote["amount_in"], E18.to_string());
+        assert_eq!(quote["tokens"], serde_json::json!([weth, usdc, token]));
+        assert_eq!(
+            quote["pairs"],
+            serde_json::json!([testing::address(1), testing::address(2)])
+        );
+        assert!(quote["min_amount_out"].is_string());
+
+        let amount_out = quote["amount_out"].as_str().unwrap_or_default().to_string();
+        assert!(U256::from_dec_str(&amount_out)? < U256::from(1_000 * E18));
+
+        let (status, route) = get_json(
+            &app,
+            &format!("/route?from={}&to={}&amount={}", weth, token, E18),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(route["amount_out"], amount_out.as_str());
+        assert!(route["min_amount_out"].is_string());
+        assert!(route.get("max_amount_in").is_none());
+        assert_eq!(route["hops"][0]["token_in"]["symbol"], "WETH");
+        assert_eq!(route["hops"][0]["factory"], "Uniswap");
+        assert_eq!(route["hops"][1]["token_out"]["symbol"], "TKN");
+
+        let (status, route) = get_json(
+            &app,
+            &format!(
+                "/route?from={}&to={}&amount={}&exact_out=true",
+                weth, token, amount_out
+            ),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(route["amount_out"], amount_out.as_str());
+        assert!(route["max_amount_in"].is_string());
+
+        let (status, routes) = get_json(
+            &app,
+            &format!("/routes?from={}&to={}&amount={}&count=2", weth, token, E18),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(routes[0]["amount_out"], amount_out.as_str());
+        assert_eq!(routes[0]["hops"], 2);
+
+        let (status, tokens) = get_json(&app, "/tokens").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tokens.as_array().map(Vec::len), Some(4));
+
+        let (status, pair) = get_json(&app, &format!("/pairs/{}", testing::address(1))).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(pair["token0"]["symbol"], "WETH");
+        assert_eq!(pair["reserve1"], (2_000_000 * E6).to_string());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_statuses() -> eyre::Result<()> {
+        let app = test_app();
+        let (weth, token, unpaired) = (address(WETH), address(TOKEN), address(UNPAIRED));
+        let unknown = format!("{:?}", Address::repeat_byte(0xee));
+
+        let cases = [
+            (
+                format!("/quote?from={}&to={}&amount=1000", weth, unknown),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                format!("/quote?from={}&to={}&amount=1000", weth, unpaired),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                format!("/quote?from={}&to={}&amount=1000", weth, weth),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                format!("/quote?from={}&to={}&amount=abc", weth, token),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                format!(
+                    "/quote?from={}&to={}&amount=1000&slippage_bps=10001",
+                    weth, token
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                format!(
+                    "/route?from={}&to={}&amount=1000&slippage_bps=10001",
+                    weth, token
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                format!("/routes?from={}&to={}&amount=1000&count=0", weth, token),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                format!("/routes?from={}&to={}&amount=1000&count=11", weth, token),
+                StatusCode::BAD_REQUEST,
+            ),
+            (format!("/pairs/{}", unknown), StatusCode::NOT_FOUND),
+        ];
+
+        for (uri, expected) in cases {
+            let (status, body) = get_json(&app, &uri).await?;
+            assert_eq!(status, expected, "{}", uri);
+            assert!(body["error"].is_string(), "{}", uri);
+        }
+
+        Ok(())
+    }
+
+    /// Requires the database at `DATABASE_URL` with at least one indexed pair
+    /// with reserves.
+    #[tokio::test]
+    #[ignore = "requires a local Postgres"]
+    async fn test_api() -> eyre::Result<()> {
+        let database = DB::new().await?;
+        let state = GraphState::load(&database, &GraphFilters::default(), None).await?;
+
+        let tokens_count = state.tokens.len();
+        let (token_a, token_b, pairs) = state
+            .graph
+            .graph()
+            .all_edges()
+            .next()
+            .ok_or_else(|| eyre::eyre!("No pairs are indexed"))?;
+        let pair_address = pairs[0].address.trim_end().to_string();
+        let token_a = state.tokens[&token_a].address.trim_end().to_string();
+        let token_b = state.tokens[&token_b].address.trim_end().to_string();
+
+        let app = router(Arc::new(RwLock::new(state)));
+
+        let (status, tokens) = get_json(&app, "/tokens").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tokens.as_array().map(Vec::len), Some(tokens_count));
+
+        let (status, pair) = get_json(&app, &format!("/pairs/{}", pair_address)).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(pair["address"], pair_address.as_str());
+
+        let (status, _) = get_json(&app, &format!("/pairs/{:?}", Address::zero())).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, quote) = get_json(
+            &app,
+            &format!("/quote?from={}&to={}&amount=1000", token_a, token_b),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(quote["amount_in"], "1000");
+
+        let (status, route) = get_json(
+            &app,
+            &format!(
+                "/route?from={}&to={}&amount={}&exact_out=true",
+                token_a,
+                token_b,
+                quote["amount_out"].as_str().unwrap_or_default()
+            ),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(route["amount_out"], quote["amount_out"]);
+        assert!(route["max_amount_in"].is_string());
+
+        let (status, routes) = get_json(
+            &app,
+            &format!(
+                "/routes?from={}&to={}&amount=1000&count=2",
+                token_a, token_b
+            ),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(routes[0]["amount_out"], quote["amount_out"]);
+
+        let (status, _) = get_json(
+            &app,
+            &format!("/quote?from={}&to={}&amount=abc", token_a, token_b),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
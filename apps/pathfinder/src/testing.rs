@@ -0,0 +1,108 @@
+//! Fixtures of the pairs graph and the Anvil client for the tests.
+
+use std::str::FromStr;
+
+use color_eyre::eyre;
+use config::Fee;
+use database::{tokens::TokenEntry, PairsStreamEntry};
+use ethers::{
+    providers::{Http, Provider},
+    types::{Address, U256},
+};
+use petgraph::prelude::UnGraphMap;
+use sqlx::types::BigDecimal;
+
+use crate::actions::find::{PairEdge, PairsGraph};
+
+/// Pair of the default fee with the address derived from its id.
+pub fn pair(id: i32, token0: i32, reserve0: u128, reserve1: u128) -> PairEdge {
+    PairEdge {
+        id,
+        address: address(id),
+        factory_id: 1,
+        fee: Fee::default(),
+        token0_id: token0,
+        reserve0: U256::from(reserve0),
+        reserve1: U256::from(reserve1),
+    }
+}
+
+/// Build the graph of the pairs given as `(id, token0, token1, reserve0,
+/// reserve1)`.
+pub fn graph(pairs: &[(i32, i32, i32, u128, u128)]) -> PairsGraph {
+    let mut graph: PairsGraph = UnGraphMap::new();
+
+    for &(id, token0, token1, reserve0, reserve1) in pairs {
+        let edge = pair(id, token0, reserve0, reserve1);
+
+        match graph.edge_weight_mut(token0, token1) {
+            Some(pairs) => pairs.push(edge),
+            None => {
+                graph.add_edge(token0, token1, vec![edge]);
+            }
+        }
+    }
+
+    graph
+}
+
+/// Address of the token or the pair derived from its id, stored the way the
+/// database does.
+pub fn address(id: i32) -> String {
+    database::format_address(Address::from_low_u64_be(id as u64))
+}
+
+/// Stored pair of the default fee with its latest reserves, as
+/// [`database::DB::pairs_stream`] returns it.
+pub fn stream_entry(
+    id: i32,
+    token0: i32,
+    token1: i32,
+    reserve0: u128,
+    reserve1: u128,
+) -> PairsStreamEntry {
+    PairsStreamEntry {
+        pair_id: id,
+        pair_address: address(id),
+        factory_id: 1,
+        fee_numerator: 997,
+        fee_denominator: 1000,
+        token0_id: token0,
+        token0_short_address: address(token0),
+        token1_id: token1,
+        token1_short_address: address(token1),
+        reserves_id: id,
+        block_height: 1,
+        reserve0: BigDecimal::from_str(&reserve0.to_string()).expect("integer is a valid decimal"),
+        reserve1: BigDecimal::from_str(&reserve1.to_string()).expect("integer is a valid decimal"),
+    }
+}
+
+/// Stored token with the complete metadata, its address is derived from the
+/// id with the bit 32 set, so it doesn't collide with the addresses of the
+/// pairs.
+pub fn token(id: i32, symbol: &str, decimals: i32) -> TokenEntry {
+    TokenEntry {
+        id,
+        address: database::format_address(Address::from_low_u64_be(1 << 32 | id as u64)),
+        name: symbol.to_string(),
+        symbol: symbol.to_string(),
+        decimals,
+        metadata_status: "complete".to_string(),
+        transfer_tax_bps: None,
+        rebasing: None,
+    }
+}
+
+/// Client of the local Anvil node at `ETH_RPC_URL`, `127.0.0.1:8545` by
+/// default, for the tests ignored by default.
+pub fn anvil_provider() -> eyre::Result<Provider<Http>> {
+    let url = std::env::var("ETH_RPC_URL").unwrap_or_else(|_| "127.0.0.1:8545".to_string());
+    let url = if url.contains("://") {
+        url
+    } else {
+        format!("http://{}", url)
+    };
+
+    Ok(Provider::<Http>::try_from(url.as_str())?)
+}
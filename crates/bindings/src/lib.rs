@@ -10,6 +10,7 @@ pub mod help;
 pub mod i_uniswap_v2_callee;
 pub mod i_uniswap_v2_factory;
 pub mod i_uniswap_v2_pair;
+pub mod i_uniswap_v2_router_02;
 pub mod i_uniswap_v2erc20;
 pub mod ierc20;
 pub mod math;
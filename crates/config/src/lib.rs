@@ -1,5 +1,6 @@
 use std::{io::Read, path::PathBuf, str::FromStr};
 
+use ethers::types::Address;
 use eyre::{self, Context};
 use serde::Deserialize;
 
@@ -7,6 +8,18 @@ use serde::Deserialize;
 pub struct Config {
     pub database: Database,
     pub logger: Logger,
+
+    /// Factories of the DEXes to index.
+    #[serde(default)]
+    pub factories: Vec<Factory>,
+
+    /// Known swap fees of the pairs which differ from the fee of their
+    /// factory.
+    #[serde(default)]
+    pub fees: Vec<PairFee>,
+
+    #[serde(default)]
+    pub pathfinder: Pathfinder,
 }
 
 #[derive(Debug, Deserialize)]
@@ -20,6 +33,164 @@ pub struct Logger {
     pub path: Option<String>,
 }
 
+/// Settings of the pathfinder.
+///
+/// ```toml
+/// [pathfinder]
+/// base_token = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
+/// stablecoins = [
+///     "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+///     "0x6B175474E89094C44Da98b954EedeAC495271d0F",
+/// ]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Pathfinder {
+    /// Token to start arbitrage cycles from and to price tokens in, usually
+    /// WETH.
+    pub base_token: Option<Address>,
+
+    /// Stablecoins pegged to USD, used to price tokens in USD.
+    #[serde(default)]
+    pub stablecoins: Vec<Address>,
+
+    #[serde(default)]
+    pub filters: GraphFilters,
+}
+
+/// Filters of the pairs used to build the graph, to keep dust pools and scam
+/// tokens out of the routes.
+///
+/// ```toml
+/// [pathfinder.filters]
+/// min_liquidity = 0.5
+/// deny_tokens = ["0x0000000000000000000000000000000000000001"]
+/// exclude_unknown = true
+/// max_transfer_tax_bps = 0
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GraphFilters {
+    /// Minimal value of both reserves of the pair in whole base tokens.
+    pub min_liquidity: Option<f64>,
+
+    /// If not empty, only pairs of the listed tokens are used.
+    #[serde(default)]
+    pub allow_tokens: Vec<Address>,
+
+    /// Pairs of the listed tokens aren't used.
+    #[serde(default)]
+    pub deny_tokens: Vec<Address>,
+
+    /// Don't use pairs of the tokens which decimals couldn't be fetched.
+    #[serde(default)]
+    pub exclude_unknown: bool,
+
+    /// Maximal transfer tax of the tokens in basis points, pairs of the
+    /// tokens taking more aren't used. Quotes don't account for the tax, so
+    /// only the tokens without it are used by default.
+    #[serde(default)]
+    pub max_transfer_tax_bps: u32,
+
+    /// Use pairs of the tokens which balances change without transfers.
+    #[serde(default)]
+    pub allow_rebasing: bool,
+}
+
+/// Factory of the Uniswap V2 like DEX.
+///
+/// ```toml
+/// [[factories]]
+/// name = "uniswap-v2"
+/// address = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f"
+/// router = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D"
+/// fee = { numerator = 997, denominator = 1000 }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Factory {
+    /// Human readable name of the DEX.
+    pub name: String,
+
+    /// Address of the factory contract.
+    pub address: Address,
+
+    /// Swap fee of the pairs created by the factory.
+    #[serde(default)]
+    pub fee: Fee,
+
+    /// Address of the `UniswapV2Router02` like router of the factory, used to
+    /// build the swap transactions.
+    pub router: Option<Address>,
+}
+
+/// Swap fee of the particular pair, overriding the fee of its factory.
+///
+/// ```toml
+/// [[fees]]
+/// pair = "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"
+/// numerator = 9975
+/// denominator = 10000
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct PairFee {
+    /// Address of the pair.
+    pub pair: Address,
+
+    #[serde(flatten)]
+    pub fee: Fee,
+}
+
+/// Swap fee as the fraction of the input amount left after the fee is taken,
+/// e.g. `997 / 1000` for the 0.3% fee of Uniswap V2.
+///
+/// The fraction is checked to be at most one when deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawFee")]
+pub struct Fee {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+#[derive(Deserialize)]
+struct RawFee {
+    numerator: u32,
+    denominator: u32,
+}
+
+impl TryFrom<RawFee> for Fee {
+    type Error = String;
+
+    fn try_from(
+        RawFee {
+            numerator,
+            denominator,
+        }: RawFee,
+    ) -> Result<Self, Self::Error> {
+        if denominator == 0 {
+            return Err("fee denominator can't be zero".to_string());
+        }
+
+        if numerator > denominator {
+            return Err(format!(
+                "fee numerator {} is greater than the denominator {}",
+                numerator, denominator
+            ));
+        }
+
+        Ok(Self {
+            numerator,
+            denominator,
+        })
+    }
+}
+
+impl Default for Fee {
+    fn default() -> Self {
+        Self {
+            numerator: 997,
+            denominator: 1000,
+        }
+    }
+}
+
 impl FromStr for Config {
     type Err = eyre::Error;
 
@@ -33,7 +204,7 @@ impl FromStr for Config {
 impl Config {
     pub fn load(path: impl Into<PathBuf>) -> eyre::Result<Self> {
         let path = path.into();
-        let mut file = std::fs::File::open(&path)?;
+        let mut file = std::fs::File::open(path)?;
 
         let mut buff = String::new();
 
@@ -45,3 +216,58 @@ impl Config {
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [database]
+        url = "postgresql://localhost/dex"
+
+        [logger]
+        level = "info"
+    "#;
+
+    #[test]
+    fn test_fee_is_validated() {
+        let cases = [
+            ((997, 1000), true),
+            ((1000, 1000), true),
+            ((1, 0), false),
+            ((1001, 1000), false),
+        ];
+
+        for ((numerator, denominator), is_valid) in cases {
+            let factory = format!(
+                "{}\n[[factories]]\nname = \"Uniswap\"\naddress = \"{:?}\"\nfee = {{ numerator = {}, denominator = {} }}\n",
+                CONFIG,
+                Address::zero(),
+                numerator,
+                denominator,
+            );
+            assert_eq!(
+                factory.parse::<Config>().is_ok(),
+                is_valid,
+                "{} / {}",
+                numerator,
+                denominator
+            );
+
+            let pair = format!(
+                "{}\n[[fees]]\npair = \"{:?}\"\nnumerator = {}\ndenominator = {}\n",
+                CONFIG,
+                Address::zero(),
+                numerator,
+                denominator,
+            );
+            assert_eq!(
+                pair.parse::<Config>().is_ok(),
+                is_valid,
+                "{} / {}",
+                numerator,
+                denominator
+            );
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use sqlx::FromRow;
+use ethers::types::H256;
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    FromRow,
+};
 
 #[derive(Debug, Clone, FromRow)]
 pub struct BlockEntry {
@@ -6,8 +10,27 @@ pub struct BlockEntry {
     pub id: i32,
 
     /// Height of the block on chain
-    pub height: i32,
+    pub height: i64,
 
     /// Hash of the block
     pub hash: String,
+
+    /// Hash of the parent block, missing for blocks indexed before it was
+    /// tracked.
+    pub parent_hash: Option<String>,
+
+    /// Time the block was produced at.
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl BlockEntry {
+    /// Check if the stored block has the given hash.
+    ///
+    /// Blocks indexed before full hashes were stored have them shortened
+    /// (`0x1234…abcd`), so those are compared in the same shortened form.
+    pub fn is_hash_of(&self, hash: H256) -> bool {
+        let stored = self.hash.trim_end();
+
+        stored == format!("{:?}", hash) || stored == hash.to_string()
+    }
 }
@@ -8,4 +8,14 @@ pub struct FactoryEntry {
 
     /// Address of the factory.
     pub address: String,
+
+    /// Human readable name of the DEX.
+    pub name: Option<String>,
+
+    /// Numerator of the fraction of the input amount left after the swap fee.
+    pub fee_numerator: i32,
+
+    /// Denominator of the fraction of the input amount left after the swap
+    /// fee.
+    pub fee_denominator: i32,
 }
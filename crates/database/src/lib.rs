@@ -1,5 +1,6 @@
 use std::{env, pin::Pin};
 
+use blocks::BlockEntry;
 use ethers::{
     abi::Hash,
     types::{Address, Block},
@@ -7,16 +8,61 @@ use ethers::{
 use eyre::Context;
 use futures::Stream;
 use pairs::PairEntry;
-use sqlx::{PgConnection, FromRow, types::BigDecimal};
+use sqlx::{
+    postgres::PgListener,
+    types::{
+        chrono::{DateTime, Utc},
+        BigDecimal,
+    },
+    FromRow, PgConnection,
+};
 
 pub mod blocks;
 pub mod factories;
 pub mod pairs;
+pub mod prices;
 pub mod reserves;
 pub mod tokens;
+pub mod trades;
+
+/// Channel the `notify_reserves` trigger sends changed reserves to, see
+/// [`reserves::ReservesNotification`].
+pub const RESERVES_CHANNEL: &str = "reserves";
 
 pub type AsyncStream<'a, T> = Pin<Box<dyn Stream<Item = Result<T, sqlx::Error>> + Send + 'a>>;
 
+/// Format the address the way it's stored in the database.
+///
+/// `Display` of ethers types shortens them (`0x1234…abcd`), so the full hex
+/// representation from `Debug` is used instead.
+pub fn format_address(address: Address) -> String {
+    format!("{:?}", address)
+}
+
+/// Format the address the way it was stored before the migration `003`,
+/// shortened by `Display`.
+pub fn format_legacy_address(address: Address) -> String {
+    format!("{}", address)
+}
+
+/// Check if the stored address is in the shortened form, which can't be
+/// parsed back, see [`format_legacy_address`].
+pub fn is_legacy_address(stored: &str) -> bool {
+    stored.contains('…')
+}
+
+/// Check if the stored address, either full or shortened, is the one of
+/// given address.
+pub fn matches_address(stored: &str, address: Address) -> bool {
+    let stored = stored.trim_end();
+
+    if is_legacy_address(stored) {
+        stored == format_legacy_address(address)
+    } else {
+        stored.eq_ignore_ascii_case(&format_address(address))
+    }
+}
+
 #[derive(Clone)]
 pub struct DB {
     pool: sqlx::PgPool,
@@ -40,6 +86,8 @@ impl DB {
     }
 
     /// Insert block entry into the database and return it's id.
+    ///
+    /// And duplicated hashes, do nothing and return the previous id.
     pub async fn insert_block(&self, block: Block<Hash>) -> eyre::Result<i32> {
         let height = block
             .number
@@ -47,17 +95,23 @@ impl DB {
             .as_u64() as i64;
         let hash = block
             .hash
-            .ok_or_else(|| eyre::eyre!("Block hash is missing"))?
-            .to_string();
+            .ok_or_else(|| eyre::eyre!("Block hash is missing"))?;
+        let timestamp = DateTime::<Utc>::from_timestamp(block.timestamp.as_u64() as i64, 0)
+            .ok_or_else(|| eyre::eyre!("Block timestamp is out of range"))?;
 
         let block_record = sqlx::query!(
             r#"
-            INSERT INTO blocks (height, hash)
-            VALUES ($1, $2)
+            INSERT INTO blocks (height, hash, parent_hash, timestamp)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (hash)
+            DO UPDATE
+                SET hash = EXCLUDED.hash
             RETURNING id
             "#,
             height,
-            hash
+            format!("{:?}", hash),
+            format!("{:?}", block.parent_hash),
+            timestamp,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -65,9 +119,148 @@ impl DB {
         Ok(block_record.id)
     }
 
+    /// Return up to `limit` blocks with the biggest height, starting from the
+    /// highest one.
+    pub async fn latest_blocks(&self, limit: i64) -> eyre::Result<Vec<BlockEntry>> {
+        let blocks = sqlx::query_as!(
+            BlockEntry,
+            r#"
+            SELECT *
+            FROM blocks
+            ORDER BY height DESC, id DESC
+            LIMIT $1
+            "#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(blocks)
+    }
+
+    /// Return height of the latest stored block, or `None` if no blocks are
+    /// stored yet.
+    pub async fn latest_block_height(conn: &mut PgConnection) -> eyre::Result<Option<i64>> {
+        let record = sqlx::query!(r#"SELECT MAX(height) as height FROM blocks"#)
+            .fetch_one(conn)
+            .await?;
+
+        Ok(record.height)
+    }
+
+    /// Return height of the last block processed by the follower, or `None`
+    /// if it never ran.
+    pub async fn follower_height(conn: &mut PgConnection) -> eyre::Result<Option<i64>> {
+        let record = sqlx::query!(r#"SELECT height FROM follower_cursor"#)
+            .fetch_optional(conn)
+            .await?;
+
+        Ok(record.map(|record| record.height))
+    }
+
+    /// Store height of the last block processed by the follower.
+    pub async fn set_follower_height(conn: &mut PgConnection, height: i64) -> eyre::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO follower_cursor (height)
+            VALUES ($1)
+            ON CONFLICT (id)
+            DO UPDATE
+                SET height = EXCLUDED.height
+            "#,
+            height,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Check if a block at the height is stored, i.e. reserves of the pairs
+    /// changed on it were fetched or a snapshot of it was taken.
+    pub async fn has_block_at_height(conn: &mut PgConnection, height: i64) -> eyre::Result<bool> {
+        let record = sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM blocks WHERE height = $1) as "exists!""#,
+            height,
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(record.exists)
+    }
+
+    /// Delete blocks which are no longer canonical together with the reserves
+    /// fetched on them.
+    ///
+    /// Return the pairs which have lost their reserves, so they could be
+    /// fetched again.
+    pub async fn delete_blocks(
+        conn: &mut PgConnection,
+        block_ids: &[i32],
+    ) -> eyre::Result<Vec<PairEntry>> {
+        let pairs = sqlx::query_as!(
+            PairEntry,
+            r#"
+            WITH deleted AS (
+                DELETE FROM reserves
+                WHERE block = ANY($1)
+                RETURNING pair
+            )
+            SELECT *
+            FROM pairs
+            WHERE id IN (SELECT pair FROM deleted)
+            "#,
+            block_ids,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM token_prices
+            WHERE block = ANY($1)
+            "#,
+            block_ids,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM swaps
+            WHERE block = ANY($1)
+            "#,
+            block_ids,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM liquidity_events
+            WHERE block = ANY($1)
+            "#,
+            block_ids,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM blocks
+            WHERE id = ANY($1)
+            "#,
+            block_ids,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(pairs)
+    }
+
     /// Return number of the last indexed pair in the database for given
-    /// factory.
-    pub async fn last_indexed_pair(&self, factory_id: i32) -> eyre::Result<i32> {
+    /// factory, or `None` if no pairs are indexed yet.
+    pub async fn last_indexed_pair(&self, factory_id: i32) -> eyre::Result<Option<i32>> {
         let last_indexed_pair = sqlx::query!(
             r#"
             SELECT number
@@ -78,26 +271,55 @@ impl DB {
             "#,
             factory_id
         )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(last_indexed_pair.map(|record| record.number))
+    }
+
+    /// Return the height of the block in which the last pair of the factory
+    /// with known creation block was created.
+    pub async fn last_pair_creation_block(&self, factory_id: i32) -> eyre::Result<Option<i64>> {
+        let record = sqlx::query!(
+            r#"
+            SELECT MAX(created_at_block) as created_at_block
+            FROM pairs
+            WHERE factory = $1
+            "#,
+            factory_id
+        )
         .fetch_one(&self.pool)
         .await?;
 
-        Ok(last_indexed_pair.number)
+        Ok(record.created_at_block)
     }
 
     /// Insert factory entry into the database and return it's id.
     ///
-    /// And duplicated addresses, do nothing and return the previous id.
-    pub async fn insert_factory(&self, factory: Address) -> eyre::Result<i32> {
+    /// And duplicated addresses, update the name and fee and return the
+    /// previous id.
+    pub async fn insert_factory(
+        &self,
+        factory: Address,
+        name: &str,
+        fee_numerator: u32,
+        fee_denominator: u32,
+    ) -> eyre::Result<i32> {
         let factory_record = sqlx::query!(
             r#"
-            INSERT INTO factories (address)
-            VALUES ($1)
+            INSERT INTO factories (address, name, fee_numerator, fee_denominator)
+            VALUES ($1, $2, $3, $4)
             ON CONFLICT (address)
             DO UPDATE
-                SET address = EXCLUDED.address
+                SET name = EXCLUDED.name,
+                    fee_numerator = EXCLUDED.fee_numerator,
+                    fee_denominator = EXCLUDED.fee_denominator
             RETURNING id
             "#,
-            factory.to_string(),
+            format_address(factory),
+            name,
+            fee_numerator as i32,
+            fee_denominator as i32,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -105,6 +327,22 @@ impl DB {
         Ok(factory_record.id)
     }
 
+    /// Return all the indexed factories.
+    pub async fn factories(conn: &mut PgConnection) -> eyre::Result<Vec<factories::FactoryEntry>> {
+        let factories = sqlx::query_as!(
+            factories::FactoryEntry,
+            r#"
+            SELECT *
+            FROM factories
+            ORDER BY id
+            "#,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(factories)
+    }
+
     /// For two tokens, check if they exist in the database.
     ///
     /// Return the ids of the tokens if they exist.
@@ -119,7 +357,7 @@ impl DB {
             FROM tokens
             WHERE address = $1
             "#,
-            token0.to_string(),
+            format_address(token0),
         )
         .fetch_optional(&mut *conn)
         .await?;
@@ -130,7 +368,7 @@ impl DB {
             FROM tokens
             WHERE address = $1
             "#,
-            token1.to_string(),
+            format_address(token1),
         )
         .fetch_optional(&mut *conn)
         .await?;
@@ -140,28 +378,54 @@ impl DB {
 
     /// Insert token entry into the database and return it's id.
     ///
-    /// And duplicated addresses, do nothing and return the previous id.
+    /// On duplicated addresses return the previous id. The stored metadata is
+    /// replaced only if it's incomplete and the new one is at least partial,
+    /// so tokens fetched again after a failure get their metadata fixed.
     pub async fn insert_token(
         conn: &mut PgConnection,
         address: Address,
         name: String,
         symbol: String,
         decimals: u8,
+        metadata_status: tokens::MetadataStatus,
     ) -> eyre::Result<i32> {
+        let address = format_address(address);
+
         let token_record = sqlx::query!(
             r#"
             INSERT INTO
-                tokens (address, name, symbol, decimals)
-            VALUES ($1, $2, $3, $4)
+                tokens (address, name, symbol, decimals, metadata_status)
+            VALUES ($1, $2, $3, $4, $5)
             ON CONFLICT (address)
             DO UPDATE
-                SET address = EXCLUDED.address
+                SET name = EXCLUDED.name,
+                    symbol = EXCLUDED.symbol,
+                    decimals = EXCLUDED.decimals,
+                    metadata_status = EXCLUDED.metadata_status
+                WHERE tokens.metadata_status <> 'complete' AND EXCLUDED.metadata_status <> 'guessed'
             RETURNING id
             "#,
-            address.to_string(),
+            address,
             name,
             symbol,
             decimals as i32,
+            metadata_status.as_str(),
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(token_record) = token_record {
+            return Ok(token_record.id);
+        }
+
+        // The stored metadata is kept, so no row is returned.
+        let token_record = sqlx::query!(
+            r#"
+            SELECT id
+            FROM tokens
+            WHERE address = $1
+            "#,
+            address,
         )
         .fetch_one(&mut *conn)
         .await?;
@@ -177,19 +441,21 @@ impl DB {
         token0_id: i32,
         token1_id: i32,
         factory_id: i32,
+        created_at_block: Option<i64>,
     ) -> eyre::Result<i32> {
         let pair_record = sqlx::query!(
             r#"
             INSERT INTO
-                pairs (address, token0, token1, factory, number)
-            VALUES ($1, $2, $3, $4, $5)
+                pairs (address, token0, token1, factory, number, created_at_block)
+            VALUES ($1, $2, $3, $4, $5, $6)
             RETURNING id
             "#,
-            pair_address.to_string(),
+            format_address(pair_address),
             token0_id,
             token1_id,
             factory_id,
-            number
+            number,
+            created_at_block,
         )
         .fetch_one(&mut *conn)
         .await?;
@@ -197,10 +463,61 @@ impl DB {
         Ok(pair_record.id)
     }
 
+    /// Override the swap fee of the pair with given address.
+    ///
+    /// Return `false` if the pair isn't indexed.
+    pub async fn set_pair_fee(
+        conn: &mut PgConnection,
+        pair_address: Address,
+        fee_numerator: u32,
+        fee_denominator: u32,
+    ) -> eyre::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE pairs
+            SET fee_numerator = $2, fee_denominator = $3
+            WHERE address = $1
+            "#,
+            format_address(pair_address),
+            fee_numerator as i32,
+            fee_denominator as i32,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Store the behaviour of the token transfers found by simulating them.
+    pub async fn set_token_transfer_flags(
+        conn: &mut PgConnection,
+        token_id: i32,
+        transfer_tax_bps: u32,
+        rebasing: bool,
+    ) -> eyre::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE tokens
+            SET transfer_tax_bps = $2, rebasing = $3
+            WHERE id = $1
+            "#,
+            token_id,
+            transfer_tax_bps as i32,
+            rebasing,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
     /// Insert reserve entry into the database and return it's id.
     ///
-    /// NOTE: The reserves are stored in the database as i64, but the input is u128,
-    /// so the conversion is done here.
+    /// If the reserves of the pair are already stored for the block, they
+    /// are overwritten.
+    ///
+    /// NOTE: The reserves are stored in the database as `NUMERIC`, so the
+    /// conversion from u128 is done here without losing precision.
     pub async fn insert_reserves(
         conn: &mut PgConnection,
         pair_id: i32,
@@ -213,11 +530,15 @@ impl DB {
             INSERT INTO
                 reserves (pair, reserve0, reserve1, block)
             VALUES ($1, $2, $3, $4)
+            ON CONFLICT (pair, block)
+            DO UPDATE
+                SET reserve0 = EXCLUDED.reserve0,
+                    reserve1 = EXCLUDED.reserve1
             RETURNING id
             "#,
             pair_id,
-            reserve0 as i64,
-            reserve1 as i64,
+            reserves::reserve_to_decimal(reserve0),
+            reserves::reserve_to_decimal(reserve1),
             block_id
         )
         .fetch_one(&mut *conn)
@@ -226,60 +547,207 @@ impl DB {
         Ok(reserve_record.id)
     }
 
-    pub async fn first_factory_id(
-        conn: &mut PgConnection,
-    ) -> eyre::Result<i32> {
-        let factory_record = sqlx::query!(
+    /// Return reserves entries which could have been truncated before the
+    /// reserves were stored losslessly.
+    ///
+    /// Reserves were converted to i64 by wrapping, so the ones above
+    /// `i64::MAX` became negative, but the ones above `u64::MAX` could end up
+    /// positive, so `all` returns every entry.
+    pub async fn suspicious_reserves(
+        &self,
+        all: bool,
+    ) -> eyre::Result<Vec<reserves::SuspiciousReserveEntry>> {
+        let entries = sqlx::query_as!(
+            reserves::SuspiciousReserveEntry,
             r#"
-            SELECT id
-            FROM factories
-            ORDER BY id
-            LIMIT 1
+            SELECT
+                reserves.id as id,
+                pairs.id as pair_id,
+                pairs.address as pair_address,
+                pairs.factory as factory_id,
+                pairs.number as pair_number,
+                blocks.height as block_height
+            FROM reserves
+            JOIN pairs ON reserves.pair = pairs.id
+            JOIN blocks ON reserves.block = blocks.id
+            WHERE $1 OR reserves.reserve0 < 0 OR reserves.reserve1 < 0
+            ORDER BY reserves.id
             "#,
+            all,
         )
-        .fetch_one(&mut *conn)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(factory_record.id)
+        Ok(entries)
     }
 
-    pub async fn pairs_stream<'a>(
-        conn: &'a mut PgConnection,
-    ) -> eyre::Result<AsyncStream<'a, PairsStreamEntry>> {
-        let factory_id = Self::first_factory_id(conn).await?;
+    /// Overwrite values of the reserves entry.
+    pub async fn update_reserves(
+        conn: &mut PgConnection,
+        reserves_id: i32,
+        reserve0: u128,
+        reserve1: u128,
+    ) -> eyre::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE reserves
+            SET reserve0 = $2, reserve1 = $3
+            WHERE id = $1
+            "#,
+            reserves_id,
+            reserves::reserve_to_decimal(reserve0),
+            reserves::reserve_to_decimal(reserve1),
+        )
+        .execute(&mut *conn)
+        .await?;
 
+        Ok(())
+    }
+
+    /// Stream pairs of all the factories together with their latest known
+    /// reserves, or the latest ones at the block height `at_block`. Pairs
+    /// without reserves by that block are skipped.
+    pub async fn pairs_stream(
+        conn: &mut PgConnection,
+        at_block: Option<i64>,
+    ) -> eyre::Result<AsyncStream<'_, PairsStreamEntry>> {
         let stream = sqlx::query_as!(
             PairsStreamEntry,
             r#"--sql
             SELECT
                 pairs.id as pair_id,
+                pairs.address as pair_address,
+                pairs.factory as factory_id,
+                COALESCE(pairs.fee_numerator, factories.fee_numerator) as "fee_numerator!",
+                COALESCE(pairs.fee_denominator, factories.fee_denominator) as "fee_denominator!",
                 tokens0.id as token0_id,
                 tokens0.address as token0_short_address,
                 tokens1.id as token1_id,
                 tokens1.address as token1_short_address,
+                reserves.id as reserves_id,
+                reserves.height as block_height,
                 reserves.reserve0 as reserve0,
                 reserves.reserve1 as reserve1
             FROM pairs
+            JOIN factories ON pairs.factory = factories.id
             JOIN tokens as tokens0 ON pairs.token0 = tokens0.id
             JOIN tokens as tokens1 ON pairs.token1 = tokens1.id
-            JOIN reserves ON pairs.id = reserves.pair AND reserves.block = (
-                SELECT blocks.id
-                FROM blocks
-                ORDER BY blocks.id DESC
+            JOIN LATERAL (
+                SELECT reserves.id, blocks.height, reserves.reserve0, reserves.reserve1
+                FROM reserves
+                JOIN blocks ON reserves.block = blocks.id
+                WHERE reserves.pair = pairs.id
+                    AND ($1::BIGINT IS NULL OR blocks.height <= $1)
+                ORDER BY blocks.height DESC
                 LIMIT 1
-            )
-            WHERE factory = $1
+            ) AS reserves ON TRUE
             "#,
-            factory_id,
+            at_block,
         )
         .fetch(conn);
 
         Ok(stream)
     }
 
+    /// Return the pair with its latest known reserves, or `None` if the pair
+    /// or its reserves aren't stored.
+    pub async fn pair_with_reserves(
+        conn: &mut PgConnection,
+        pair_id: i32,
+    ) -> eyre::Result<Option<PairsStreamEntry>> {
+        let pair = sqlx::query_as!(
+            PairsStreamEntry,
+            r#"--sql
+            SELECT
+                pairs.id as pair_id,
+                pairs.address as pair_address,
+                pairs.factory as factory_id,
+                COALESCE(pairs.fee_numerator, factories.fee_numerator) as "fee_numerator!",
+                COALESCE(pairs.fee_denominator, factories.fee_denominator) as "fee_denominator!",
+                tokens0.id as token0_id,
+                tokens0.address as token0_short_address,
+                tokens1.id as token1_id,
+                tokens1.address as token1_short_address,
+                reserves.id as reserves_id,
+                reserves.height as block_height,
+                reserves.reserve0 as reserve0,
+                reserves.reserve1 as reserve1
+            FROM pairs
+            JOIN factories ON pairs.factory = factories.id
+            JOIN tokens as tokens0 ON pairs.token0 = tokens0.id
+            JOIN tokens as tokens1 ON pairs.token1 = tokens1.id
+            JOIN LATERAL (
+                SELECT reserves.id, blocks.height, reserves.reserve0, reserves.reserve1
+                FROM reserves
+                JOIN blocks ON reserves.block = blocks.id
+                WHERE reserves.pair = pairs.id
+                ORDER BY blocks.height DESC
+                LIMIT 1
+            ) AS reserves ON TRUE
+            WHERE pairs.id = $1
+            "#,
+            pair_id,
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(pair)
+    }
+
+    /// Return reserves entries of the blocks from `min_height` ordered by the
+    /// height, to catch up with the reserves stored while notifications
+    /// weren't listened to.
+    pub async fn reserves_since(
+        conn: &mut PgConnection,
+        min_height: i64,
+    ) -> eyre::Result<Vec<reserves::ReservesUpdate>> {
+        let reserves = sqlx::query_as!(
+            reserves::ReservesUpdate,
+            r#"
+            SELECT reserves.id, reserves.pair, blocks.height as block_height, reserves.reserve0, reserves.reserve1
+            FROM reserves
+            JOIN blocks ON reserves.block = blocks.id
+            WHERE blocks.height >= $1
+            ORDER BY blocks.height, reserves.id
+            "#,
+            min_height,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(reserves)
+    }
+
+    /// Start listening to the [`RESERVES_CHANNEL`].
+    pub async fn reserves_listener(&self) -> eyre::Result<PgListener> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(RESERVES_CHANNEL).await?;
+
+        Ok(listener)
+    }
+
+    /// Return pairs with id bigger than `after_id` ordered by id, so newly
+    /// indexed pairs could be picked up incrementally.
+    pub async fn pairs_after(&self, after_id: i32) -> eyre::Result<Vec<PairEntry>> {
+        let pairs = sqlx::query_as!(
+            PairEntry,
+            r#"
+            SELECT *
+            FROM pairs
+            WHERE id > $1
+            ORDER BY id
+            "#,
+            after_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pairs)
+    }
+
     pub async fn pair_by_address(
         conn: &mut PgConnection,
-        address: Address
+        address: Address,
     ) -> eyre::Result<Option<PairEntry>> {
         let pair = sqlx::query_as!(
             PairEntry,
@@ -288,7 +756,7 @@ impl DB {
             FROM pairs
             WHERE address = $1
             "#,
-            address.to_string(),
+            format_address(address),
         )
         .fetch_optional(conn)
         .await?;
@@ -298,7 +766,7 @@ impl DB {
 
     pub async fn token_by_address(
         conn: &mut PgConnection,
-        address: Address
+        address: Address,
     ) -> eyre::Result<Option<tokens::TokenEntry>> {
         let token = sqlx::query_as!(
             tokens::TokenEntry,
@@ -307,7 +775,7 @@ impl DB {
             FROM tokens
             WHERE address = $1
             "#,
-            address.to_string(),
+            format_address(address),
         )
         .fetch_optional(conn)
         .await?;
@@ -315,9 +783,194 @@ impl DB {
         Ok(token)
     }
 
+    pub async fn tokens(conn: &mut PgConnection) -> eyre::Result<Vec<tokens::TokenEntry>> {
+        let tokens = sqlx::query_as!(
+            tokens::TokenEntry,
+            r#"
+            SELECT *
+            FROM tokens
+            ORDER BY id
+            "#,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    /// Store prices of the tokens at the block, replacing the ones computed
+    /// before in the same base token.
+    pub async fn insert_token_prices(
+        conn: &mut PgConnection,
+        block_id: i32,
+        base_token_id: i32,
+        prices: &[prices::NewTokenPrice],
+    ) -> eyre::Result<()> {
+        let tokens: Vec<i32> = prices.iter().map(|price| price.token).collect();
+        let prices_base: Vec<BigDecimal> = prices
+            .iter()
+            .map(|price| price.price_base.clone())
+            .collect();
+        let prices_usd: Vec<Option<BigDecimal>> =
+            prices.iter().map(|price| price.price_usd.clone()).collect();
+        let liquidity: Vec<Option<BigDecimal>> =
+            prices.iter().map(|price| price.liquidity.clone()).collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO
+                token_prices (token, block, base_token, price_base, price_usd, liquidity)
+            SELECT token, $1, $2, price_base, price_usd, liquidity
+            FROM UNNEST($3::INTEGER[], $4::NUMERIC[], $5::NUMERIC[], $6::NUMERIC[])
+                AS prices (token, price_base, price_usd, liquidity)
+            ON CONFLICT (token, base_token, block)
+            DO UPDATE
+                SET price_base = EXCLUDED.price_base,
+                    price_usd = EXCLUDED.price_usd,
+                    liquidity = EXCLUDED.liquidity
+            "#,
+            block_id,
+            base_token_id,
+            &tokens,
+            &prices_base,
+            &prices_usd as &[Option<BigDecimal>],
+            &liquidity as &[Option<BigDecimal>],
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Store swaps of the pairs emitted in the block, the ones already stored
+    /// are skipped.
+    pub async fn insert_swaps(
+        conn: &mut PgConnection,
+        block_id: i32,
+        swaps: &[trades::NewSwap],
+    ) -> eyre::Result<()> {
+        let pairs: Vec<i32> = swaps.iter().map(|swap| swap.pair).collect();
+        let tx_hashes: Vec<String> = swaps
+            .iter()
+            .map(|swap| format!("{:?}", swap.tx_hash))
+            .collect();
+        let log_indices: Vec<i32> = swaps.iter().map(|swap| swap.log_index as i32).collect();
+        let senders: Vec<String> = swaps
+            .iter()
+            .map(|swap| format_address(swap.sender))
+            .collect();
+        let recipients: Vec<String> = swaps
+            .iter()
+            .map(|swap| format_address(swap.recipient))
+            .collect();
+        let amounts0_in: Vec<BigDecimal> = swaps
+            .iter()
+            .map(|swap| trades::amount_to_decimal(swap.amount0_in))
+            .collect();
+        let amounts1_in: Vec<BigDecimal> = swaps
+            .iter()
+            .map(|swap| trades::amount_to_decimal(swap.amount1_in))
+            .collect();
+        let amounts0_out: Vec<BigDecimal> = swaps
+            .iter()
+            .map(|swap| trades::amount_to_decimal(swap.amount0_out))
+            .collect();
+        let amounts1_out: Vec<BigDecimal> = swaps
+            .iter()
+            .map(|swap| trades::amount_to_decimal(swap.amount1_out))
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO
+                swaps (pair, block, tx_hash, log_index, sender, recipient, amount0_in, amount1_in, amount0_out, amount1_out)
+            SELECT pair, $1, tx_hash, log_index, sender, recipient, amount0_in, amount1_in, amount0_out, amount1_out
+            FROM UNNEST(
+                $2::INTEGER[], $3::TEXT[], $4::INTEGER[], $5::TEXT[], $6::TEXT[],
+                $7::NUMERIC[], $8::NUMERIC[], $9::NUMERIC[], $10::NUMERIC[]
+            ) AS swaps (pair, tx_hash, log_index, sender, recipient, amount0_in, amount1_in, amount0_out, amount1_out)
+            ON CONFLICT (tx_hash, log_index)
+            DO NOTHING
+            "#,
+            block_id,
+            &pairs,
+            &tx_hashes,
+            &log_indices,
+            &senders,
+            &recipients,
+            &amounts0_in,
+            &amounts1_in,
+            &amounts0_out,
+            &amounts1_out,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Store mints and burns of the pairs emitted in the block, the ones
+    /// already stored are skipped.
+    pub async fn insert_liquidity_events(
+        conn: &mut PgConnection,
+        block_id: i32,
+        events: &[trades::NewLiquidityEvent],
+    ) -> eyre::Result<()> {
+        let pairs: Vec<i32> = events.iter().map(|event| event.pair).collect();
+        let tx_hashes: Vec<String> = events
+            .iter()
+            .map(|event| format!("{:?}", event.tx_hash))
+            .collect();
+        let log_indices: Vec<i32> = events.iter().map(|event| event.log_index as i32).collect();
+        let kinds: Vec<&str> = events.iter().map(|event| event.kind.as_str()).collect();
+        let senders: Vec<String> = events
+            .iter()
+            .map(|event| format_address(event.sender))
+            .collect();
+        let recipients: Vec<Option<String>> = events
+            .iter()
+            .map(|event| event.recipient.map(format_address))
+            .collect();
+        let amounts0: Vec<BigDecimal> = events
+            .iter()
+            .map(|event| trades::amount_to_decimal(event.amount0))
+            .collect();
+        let amounts1: Vec<BigDecimal> = events
+            .iter()
+            .map(|event| trades::amount_to_decimal(event.amount1))
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO
+                liquidity_events (pair, block, tx_hash, log_index, kind, sender, recipient, amount0, amount1)
+            SELECT pair, $1, tx_hash, log_index, kind, sender, recipient, amount0, amount1
+            FROM UNNEST(
+                $2::INTEGER[], $3::TEXT[], $4::INTEGER[], $5::TEXT[], $6::TEXT[], $7::TEXT[],
+                $8::NUMERIC[], $9::NUMERIC[]
+            ) AS events (pair, tx_hash, log_index, kind, sender, recipient, amount0, amount1)
+            ON CONFLICT (tx_hash, log_index)
+            DO NOTHING
+            "#,
+            block_id,
+            &pairs,
+            &tx_hashes,
+            &log_indices,
+            &kinds as &[&str],
+            &senders,
+            &recipients as &[Option<String>],
+            &amounts0,
+            &amounts1,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn token_by_id(
         conn: &mut PgConnection,
-        id: i32
+        id: i32,
     ) -> eyre::Result<Option<tokens::TokenEntry>> {
         let token = sqlx::query_as!(
             tokens::TokenEntry,
@@ -333,6 +986,222 @@ impl DB {
 
         Ok(token)
     }
+
+    /// Store the full address of the factory stored in the shortened form.
+    ///
+    /// If the factory is already stored with the full address, its pairs are
+    /// moved to that entry and the legacy one is deleted. Return `true` in
+    /// that case.
+    pub async fn migrate_factory_address(
+        conn: &mut PgConnection,
+        legacy_id: i32,
+        address: Address,
+    ) -> eyre::Result<bool> {
+        let existing = sqlx::query!(
+            r#"
+            SELECT id
+            FROM factories
+            WHERE address = $1
+            "#,
+            format_address(address),
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(existing) = existing else {
+            sqlx::query!(
+                r#"
+                UPDATE factories
+                SET address = $2
+                WHERE id = $1
+                "#,
+                legacy_id,
+                format_address(address),
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            return Ok(false);
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE pairs
+            SET factory = $2
+            WHERE factory = $1
+            "#,
+            legacy_id,
+            existing.id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM factories
+            WHERE id = $1
+            "#,
+            legacy_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Store the full address of the token stored in the shortened form.
+    ///
+    /// If the token is already stored with the full address, the pairs of
+    /// the legacy entry are moved to that one, and the legacy entry is
+    /// deleted together with its prices. Return `true` in that case.
+    pub async fn migrate_token_address(
+        conn: &mut PgConnection,
+        legacy_id: i32,
+        address: Address,
+    ) -> eyre::Result<bool> {
+        let existing = sqlx::query!(
+            r#"
+            SELECT id
+            FROM tokens
+            WHERE address = $1
+            "#,
+            format_address(address),
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(existing) = existing else {
+            sqlx::query!(
+                r#"
+                UPDATE tokens
+                SET address = $2
+                WHERE id = $1
+                "#,
+                legacy_id,
+                format_address(address),
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            return Ok(false);
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE pairs
+            SET token0 = CASE WHEN token0 = $1 THEN $2 ELSE token0 END,
+                token1 = CASE WHEN token1 = $1 THEN $2 ELSE token1 END
+            WHERE token0 = $1 OR token1 = $1
+            "#,
+            legacy_id,
+            existing.id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        // Prices are computed again from the reserves.
+        sqlx::query!(
+            r#"
+            DELETE FROM token_prices
+            WHERE token = $1 OR base_token = $1
+            "#,
+            legacy_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM tokens
+            WHERE id = $1
+            "#,
+            legacy_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Store the full address of the pair stored in the shortened form.
+    ///
+    /// If the pair is already indexed again with the full address, the
+    /// legacy entry is deleted together with its reserves and trades, which
+    /// are stored for the new entry. Return `true` in that case.
+    pub async fn migrate_pair_address(
+        conn: &mut PgConnection,
+        legacy_id: i32,
+        address: Address,
+    ) -> eyre::Result<bool> {
+        let existing = sqlx::query!(
+            r#"
+            SELECT id
+            FROM pairs
+            WHERE address = $1
+            "#,
+            format_address(address),
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if existing.is_none() {
+            sqlx::query!(
+                r#"
+                UPDATE pairs
+                SET address = $2
+                WHERE id = $1
+                "#,
+                legacy_id,
+                format_address(address),
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM reserves
+            WHERE pair = $1
+            "#,
+            legacy_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM swaps
+            WHERE pair = $1
+            "#,
+            legacy_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM liquidity_events
+            WHERE pair = $1
+            "#,
+            legacy_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM pairs
+            WHERE id = $1
+            "#,
+            legacy_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(true)
+    }
 }
 
 #[derive(Debug, FromRow)]
@@ -340,14 +1209,66 @@ pub struct PairsStreamEntry {
     /// Unique identifier of the pair inside the database.
     pub pair_id: i32,
 
+    /// Address of the pair.
+    pub pair_address: String,
+
+    /// Id of the factory the pair belongs to.
+    pub factory_id: i32,
+
+    /// Numerator of the swap fee of the pair, or of its factory if the pair
+    /// doesn't override it.
+    pub fee_numerator: i32,
+
+    /// Denominator of the swap fee of the pair, or of its factory if the
+    /// pair doesn't override it.
+    pub fee_denominator: i32,
+
     pub token0_id: i32,
     pub token0_short_address: String,
     pub token1_id: i32,
     pub token1_short_address: String,
 
+    /// Id of the latest reserves entry of the pair.
+    pub reserves_id: i32,
+
+    /// Height of the block the latest reserves were fetched on.
+    pub block_height: i64,
+
     /// Amount of token0 in the pair.
     pub reserve0: BigDecimal,
 
     /// Amount of token1 in the pair.
     pub reserve1: BigDecimal,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_address() {
+        let address: Address = "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"
+            .parse()
+            .unwrap();
+        let other: Address = "0xb4e100000000000000000000000000000000c9dc"
+            .parse()
+            .unwrap();
+
+        let legacy = format_legacy_address(address);
+        assert_eq!(legacy, "0xb4e1…c9dc");
+        assert!(is_legacy_address(&legacy));
+        assert!(!is_legacy_address(&format_address(address)));
+
+        // Stored as `CHAR(42)`, so padded with spaces.
+        assert!(matches_address(&format!("{:<42}", legacy), address));
+        assert!(matches_address(&format_address(address), address));
+        assert!(matches_address(
+            "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc",
+            address
+        ));
+
+        // Shortened form of a different address can't be told apart.
+        assert!(matches_address(&legacy, other));
+        assert!(!matches_address(&format_address(address), other));
+    }
+}
@@ -17,4 +17,15 @@ pub struct PairEntry {
     pub token0: i32,
     /// Address of the token1.
     pub token1: i32,
+
+    /// Height of the block the pair was created in, if known.
+    pub created_at_block: Option<i64>,
+
+    /// Numerator of the swap fee of the pair, if it differs from the
+    /// factory one.
+    pub fee_numerator: Option<i32>,
+
+    /// Denominator of the swap fee of the pair, if it differs from the
+    /// factory one.
+    pub fee_denominator: Option<i32>,
 }
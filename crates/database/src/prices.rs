@@ -0,0 +1,32 @@
+use sqlx::{types::BigDecimal, FromRow};
+
+/// Price of the token at the block.
+#[derive(Debug, Clone, FromRow)]
+pub struct TokenPriceEntry {
+    pub id: i32,
+
+    pub token: i32,
+    pub block: i32,
+
+    /// Token the price is computed in.
+    pub base_token: i32,
+
+    /// Price of a whole token in whole base tokens.
+    pub price_base: BigDecimal,
+
+    /// Price of a whole token in USD, if a stablecoin is reachable.
+    pub price_usd: Option<BigDecimal>,
+
+    /// Liquidity of the path the price is taken from, in whole base tokens,
+    /// not set for the base token itself.
+    pub liquidity: Option<BigDecimal>,
+}
+
+/// New price of the token to store.
+#[derive(Debug, Clone)]
+pub struct NewTokenPrice {
+    pub token: i32,
+    pub price_base: BigDecimal,
+    pub price_usd: Option<BigDecimal>,
+    pub liquidity: Option<BigDecimal>,
+}
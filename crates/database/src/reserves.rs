@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use sqlx::{types::BigDecimal, FromRow};
 
 #[derive(Debug, Clone, FromRow)]
@@ -10,3 +12,158 @@ pub struct ReserveEntry {
     pub reserve0: BigDecimal,
     pub reserve1: BigDecimal,
 }
+
+/// Reserves entry which should be fetched again, as its value could have been
+/// truncated.
+#[derive(Debug, Clone, FromRow)]
+pub struct SuspiciousReserveEntry {
+    /// Id of the reserves entry.
+    pub id: i32,
+
+    /// Id of the pair the reserves belong to.
+    pub pair_id: i32,
+
+    /// Address of the pair, shortened if it was indexed before the full
+    /// addresses were stored.
+    pub pair_address: String,
+
+    /// Id of the factory of the pair.
+    pub factory_id: i32,
+
+    /// Number of the pair in the factory.
+    pub pair_number: i32,
+
+    /// Height of the block the reserves were fetched on.
+    pub block_height: i64,
+}
+
+/// Reserves of the pair stored at the block.
+#[derive(Debug, Clone, FromRow)]
+pub struct ReservesUpdate {
+    /// Id of the reserves entry.
+    pub id: i32,
+
+    /// Id of the pair.
+    pub pair: i32,
+
+    /// Height of the block the reserves were fetched on.
+    pub block_height: i64,
+
+    pub reserve0: BigDecimal,
+    pub reserve1: BigDecimal,
+}
+
+/// Change of the reserves sent to the [`RESERVES_CHANNEL`](crate::RESERVES_CHANNEL).
+#[derive(Debug, Clone)]
+pub enum ReservesNotification {
+    /// Reserves were inserted or updated.
+    Updated(ReservesUpdate),
+
+    /// Reserves were deleted, e.g. with an orphaned block, so the latest
+    /// reserves of the pair should be loaded again.
+    Deleted { id: i32, pair: i32 },
+}
+
+impl FromStr for ReservesNotification {
+    type Err = eyre::Error;
+
+    /// Parse the payload sent by the `notify_reserves` trigger.
+    fn from_str(payload: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = payload.split(',').collect();
+
+        let notification = match parts.as_slice() {
+            [id, pair] => Self::Deleted {
+                id: id.parse()?,
+                pair: pair.parse()?,
+            },
+            [id, pair, block_height, reserve0, reserve1] => Self::Updated(ReservesUpdate {
+                id: id.parse()?,
+                pair: pair.parse()?,
+                block_height: block_height.parse()?,
+                reserve0: BigDecimal::from_str(reserve0)?,
+                reserve1: BigDecimal::from_str(reserve1)?,
+            }),
+            _ => eyre::bail!("Malformed reserves notification: {}", payload),
+        };
+
+        Ok(notification)
+    }
+}
+
+/// Convert the reserve to the `NUMERIC` value without losing precision.
+pub fn reserve_to_decimal(reserve: u128) -> BigDecimal {
+    BigDecimal::from_str(&reserve.to_string()).expect("integer is always a valid decimal")
+}
+
+/// Convert the stored reserve back, return `None` if it's negative,
+/// fractional or doesn't fit into `u128`.
+pub fn decimal_to_reserve(reserve: &BigDecimal) -> Option<u128> {
+    if !reserve.is_integer() {
+        return None;
+    }
+
+    reserve.with_scale(0).to_string().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Maximum value of `uint112` returned by `getReserves`.
+    const MAX_UINT112: u128 = (1 << 112) - 1;
+
+    #[test]
+    fn test_reserves_conversion_is_lossless() {
+        let values = [
+            0,
+            1,
+            i64::MAX as u128,
+            i64::MAX as u128 + 1,
+            u64::MAX as u128,
+            u64::MAX as u128 + 1,
+            MAX_UINT112,
+            u128::MAX,
+        ];
+
+        for value in values {
+            let decimal = reserve_to_decimal(value);
+
+            assert_eq!(decimal.to_string(), value.to_string());
+            assert_eq!(decimal_to_reserve(&decimal), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_parse_notification() {
+        let notification: ReservesNotification =
+            "7,3,120,340282366920938463463374607431768211455,0"
+                .parse()
+                .unwrap();
+
+        let ReservesNotification::Updated(update) = notification else {
+            panic!("Expected updated reserves, got {:?}", notification);
+        };
+        assert_eq!((update.id, update.pair, update.block_height), (7, 3, 120));
+        assert_eq!(decimal_to_reserve(&update.reserve0), Some(u128::MAX));
+        assert_eq!(decimal_to_reserve(&update.reserve1), Some(0));
+
+        let notification: ReservesNotification = "7,3".parse().unwrap();
+        assert!(matches!(
+            notification,
+            ReservesNotification::Deleted { id: 7, pair: 3 }
+        ));
+
+        assert!("7,3,120".parse::<ReservesNotification>().is_err());
+    }
+
+    #[test]
+    fn test_invalid_reserves_are_rejected() {
+        let values = ["-1", "0.5", "340282366920938463463374607431768211456"];
+
+        for value in values {
+            let decimal = BigDecimal::from_str(value).unwrap();
+
+            assert_eq!(decimal_to_reserve(&decimal), None, "{}", value);
+        }
+    }
+}
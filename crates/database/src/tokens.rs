@@ -17,4 +17,51 @@ pub struct TokenEntry {
 
     /// Decimals of the token.
     pub decimals: i32,
+
+    /// How the metadata was obtained, one of [`MetadataStatus`].
+    pub metadata_status: String,
+
+    /// Share of the transferred amount taken by the token in basis points,
+    /// `None` if the token isn't classified yet.
+    pub transfer_tax_bps: Option<i32>,
+
+    /// Whether the balances of the holders change without transfers, `None`
+    /// if the token isn't classified yet.
+    pub rebasing: Option<bool>,
+}
+
+impl TokenEntry {
+    /// Check if the decimals of the token couldn't be fetched and were
+    /// guessed, so its amounts and prices can't be trusted.
+    pub fn has_unknown_metadata(&self) -> bool {
+        self.metadata_status == MetadataStatus::Guessed.as_str()
+    }
+
+    /// Check if the token is known to be classified.
+    pub fn is_classified(&self) -> bool {
+        self.transfer_tax_bps.is_some() && self.rebasing.is_some()
+    }
+}
+
+/// How the metadata of the token was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataStatus {
+    /// All the fields were decoded from the token.
+    Complete,
+
+    /// The name or the symbol is missing, but the decimals are known.
+    Partial,
+
+    /// The decimals are missing and 18 is assumed.
+    Guessed,
+}
+
+impl MetadataStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataStatus::Complete => "complete",
+            MetadataStatus::Partial => "partial",
+            MetadataStatus::Guessed => "guessed",
+        }
+    }
 }
@@ -0,0 +1,68 @@
+use std::str::FromStr;
+
+use ethers::types::{Address, H256, U256};
+use sqlx::types::BigDecimal;
+
+/// Swap of the pair read from its `Swap` event.
+#[derive(Debug, Clone)]
+pub struct NewSwap {
+    pub pair: i32,
+
+    pub tx_hash: H256,
+    pub log_index: u64,
+
+    /// Caller of the pair, usually a router.
+    pub sender: Address,
+
+    /// Receiver of the output tokens.
+    pub recipient: Address,
+
+    pub amount0_in: U256,
+    pub amount1_in: U256,
+    pub amount0_out: U256,
+    pub amount1_out: U256,
+}
+
+/// Kind of the change of the pair liquidity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiquidityEventKind {
+    /// Liquidity was added with the `Mint` event.
+    Mint,
+
+    /// Liquidity was removed with the `Burn` event.
+    Burn,
+}
+
+impl LiquidityEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiquidityEventKind::Mint => "mint",
+            LiquidityEventKind::Burn => "burn",
+        }
+    }
+}
+
+/// Liquidity added to or removed from the pair.
+#[derive(Debug, Clone)]
+pub struct NewLiquidityEvent {
+    pub pair: i32,
+
+    pub tx_hash: H256,
+    pub log_index: u64,
+
+    pub kind: LiquidityEventKind,
+
+    /// Caller of the pair, usually a router.
+    pub sender: Address,
+
+    /// Receiver of the removed tokens, `None` for mints.
+    pub recipient: Option<Address>,
+
+    pub amount0: U256,
+    pub amount1: U256,
+}
+
+/// Convert the token amount to the `NUMERIC` value without losing precision.
+pub fn amount_to_decimal(amount: U256) -> BigDecimal {
+    BigDecimal::from_str(&amount.to_string()).expect("integer is always a valid decimal")
+}
@@ -0,0 +1,5 @@
+-- Parent hash and timestamp of the blocks, used to detect chain
+-- reorganizations. Nullable, as blocks indexed before don't have them.
+ALTER TABLE blocks
+    ADD COLUMN IF NOT EXISTS parent_hash CHAR(66),
+    ADD COLUMN IF NOT EXISTS timestamp   TIMESTAMPTZ;
@@ -0,0 +1,8 @@
+-- Reserves are stored only for the blocks in which a pair has changed, so the
+-- latest reserves are looked up per pair. Only one entry per pair and block
+-- is kept.
+--
+-- NOTE: addresses are stored in full since this migration, the ones indexed
+-- before are shortened to `0x1234…abcd` and can't be parsed back. They are
+-- resolved on chain by the `migrate-addresses` command of the bootstrapper.
+CREATE UNIQUE INDEX IF NOT EXISTS reserves_pair_block_idx ON reserves (pair, block);
@@ -0,0 +1,4 @@
+-- Block in which the pair was created, known only for pairs discovered from
+-- `PairCreated` events.
+ALTER TABLE pairs
+    ADD COLUMN IF NOT EXISTS created_at_block BIGINT;
@@ -0,0 +1,6 @@
+-- Human readable name of the DEX and the swap fee of its pairs, as the
+-- fraction of the input amount left after the fee is taken.
+ALTER TABLE factories
+    ADD COLUMN IF NOT EXISTS name            TEXT,
+    ADD COLUMN IF NOT EXISTS fee_numerator   INTEGER NOT NULL DEFAULT 997,
+    ADD COLUMN IF NOT EXISTS fee_denominator INTEGER NOT NULL DEFAULT 1000;
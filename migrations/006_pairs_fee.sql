@@ -0,0 +1,4 @@
+-- Swap fee of the pair, overriding the fee of its factory if set.
+ALTER TABLE pairs
+    ADD COLUMN IF NOT EXISTS fee_numerator   INTEGER,
+    ADD COLUMN IF NOT EXISTS fee_denominator INTEGER;
@@ -0,0 +1,25 @@
+-- Prices of the tokens computed from the latest reserves known at the block
+CREATE TABLE IF NOT EXISTS token_prices (
+    id SERIAL PRIMARY KEY,
+    token INTEGER NOT NULL,
+    block INTEGER NOT NULL,
+
+    -- token the prices are computed in, usually WETH
+    base_token INTEGER NOT NULL,
+
+    -- price of a whole token in whole base tokens
+    price_base NUMERIC NOT NULL,
+
+    -- price of a whole token in USD, if a stablecoin is reachable
+    price_usd  NUMERIC,
+
+    -- liquidity of the path the price is taken from, in whole base tokens,
+    -- not set for the base token itself
+    liquidity  NUMERIC,
+
+    FOREIGN KEY (token)      REFERENCES tokens(id),
+    FOREIGN KEY (block)      REFERENCES blocks(id),
+    FOREIGN KEY (base_token) REFERENCES tokens(id),
+
+    UNIQUE (token, base_token, block)
+);
@@ -0,0 +1,25 @@
+-- Notify listeners about changed reserves, so they could be kept in memory
+-- without reloading all the pairs. Payload of new and updated reserves is
+-- `id,pair,block_height,reserve0,reserve1`, payload of deleted ones is
+-- `id,pair`.
+CREATE OR REPLACE FUNCTION notify_reserves() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('reserves', OLD.id || ',' || OLD.pair);
+        RETURN OLD;
+    END IF;
+
+    PERFORM pg_notify(
+        'reserves',
+        NEW.id || ',' || NEW.pair || ','
+            || (SELECT height FROM blocks WHERE id = NEW.block) || ','
+            || NEW.reserve0 || ',' || NEW.reserve1
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS reserves_notify ON reserves;
+CREATE TRIGGER reserves_notify
+    AFTER INSERT OR UPDATE OR DELETE ON reserves
+    FOR EACH ROW EXECUTE FUNCTION notify_reserves();
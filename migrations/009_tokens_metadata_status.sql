@@ -0,0 +1,11 @@
+-- How the metadata of the token was obtained: 'complete' if all the fields
+-- were decoded from the token, 'partial' if its name or symbol is missing and
+-- 'guessed' if its decimals are missing and 18 is assumed.
+ALTER TABLE tokens
+    ADD COLUMN IF NOT EXISTS metadata_status TEXT NOT NULL DEFAULT 'complete'
+        CHECK (metadata_status IN ('complete', 'partial', 'guessed'));
+
+-- Placeholders used to be stored for all the fields if any of them failed.
+UPDATE tokens
+SET metadata_status = 'guessed'
+WHERE name = 'unknown' AND symbol = 'unknown';
@@ -0,0 +1,9 @@
+-- Behaviour of the token transfers found by simulating them on a fork, NULL
+-- until the token is classified.
+ALTER TABLE tokens
+    -- share of the transferred amount taken by the token, in basis points,
+    -- 10000 if the token can't be transferred back to the pair
+    ADD COLUMN IF NOT EXISTS transfer_tax_bps INTEGER,
+
+    -- whether balances of the holders change without transfers
+    ADD COLUMN IF NOT EXISTS rebasing BOOLEAN;
@@ -0,0 +1,52 @@
+-- Swaps of the pairs read from their `Swap` events
+CREATE TABLE IF NOT EXISTS swaps (
+    id SERIAL PRIMARY KEY,
+    pair INTEGER NOT NULL,
+    block INTEGER NOT NULL,
+
+    tx_hash   CHAR(66) NOT NULL,
+    log_index INTEGER  NOT NULL,
+
+    -- caller of the pair, usually a router, and receiver of the output
+    sender    CHAR(42) NOT NULL,
+    recipient CHAR(42) NOT NULL,
+
+    amount0_in  NUMERIC NOT NULL,
+    amount1_in  NUMERIC NOT NULL,
+    amount0_out NUMERIC NOT NULL,
+    amount1_out NUMERIC NOT NULL,
+
+    FOREIGN KEY (pair)  REFERENCES pairs(id),
+    FOREIGN KEY (block) REFERENCES blocks(id),
+
+    UNIQUE (tx_hash, log_index)
+);
+
+CREATE INDEX IF NOT EXISTS swaps_pair_block_idx ON swaps (pair, block);
+
+-- Liquidity added to and removed from the pairs, read from their `Mint` and
+-- `Burn` events
+CREATE TABLE IF NOT EXISTS liquidity_events (
+    id SERIAL PRIMARY KEY,
+    pair INTEGER NOT NULL,
+    block INTEGER NOT NULL,
+
+    tx_hash   CHAR(66) NOT NULL,
+    log_index INTEGER  NOT NULL,
+
+    kind TEXT NOT NULL CHECK (kind IN ('mint', 'burn')),
+
+    -- caller of the pair, and receiver of the tokens for burns
+    sender    CHAR(42) NOT NULL,
+    recipient CHAR(42),
+
+    amount0 NUMERIC NOT NULL,
+    amount1 NUMERIC NOT NULL,
+
+    FOREIGN KEY (pair)  REFERENCES pairs(id),
+    FOREIGN KEY (block) REFERENCES blocks(id),
+
+    UNIQUE (tx_hash, log_index)
+);
+
+CREATE INDEX IF NOT EXISTS liquidity_events_pair_block_idx ON liquidity_events (pair, block);
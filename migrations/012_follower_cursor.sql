@@ -0,0 +1,8 @@
+-- Last block processed by the follower. Other commands store blocks too,
+-- e.g. `run` stores the block it fetched the reserves at, so the latest
+-- stored block can't tell where the follower stopped.
+CREATE TABLE IF NOT EXISTS follower_cursor (
+    -- the table holds a single row
+    id     BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    height BIGINT  NOT NULL
+);
@@ -1,15 +0,0 @@
-{ python311 }:
-python311.withPackages (ps:
-  with ps; [
-    # tools
-    python
-    pip
-    setuptools
-    wheel
-    virtualenv
-    venvShellHook
-    black
-    # libraries
-    graphviz
-    psycopg2
-  ])